@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 use rand_krull::*;
 
@@ -9,7 +9,125 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("Krull64::step_slow", move |b| b.iter(|| krull2.step_slow()));
     let mut krull3 = Krull65::new();
     c.bench_function("Krull65::step", move |b| b.iter(|| krull3.step()));
+    let mut krullx = Krull64x8::from_streams([0, 1, 2, 3, 4, 5, 6, 7]);
+    c.bench_function("Krull64x8::step", move |b| b.iter(|| krullx.step()));
+
+    // Serial loops for comparison with the interleaved lanes of fill_u64.
+    let mut buffer = [0u64; 1024];
+    let mut krull4 = Krull64::new();
+    c.bench_function("Krull64 serial (1024)", |b| {
+        b.iter(|| buffer.iter_mut().for_each(|x| *x = krull4.step()))
+    });
+    c.bench_function("Krull64::fill_u64 (1024)", |b| {
+        b.iter(|| krull4.fill_u64(&mut buffer))
+    });
+    let mut krull5 = Krull65::new();
+    c.bench_function("Krull65 serial (1024)", |b| {
+        b.iter(|| buffer.iter_mut().for_each(|x| *x = krull5.step()))
+    });
+    c.bench_function("Krull65::fill_u64 (1024)", |b| {
+        b.iter(|| krull5.fill_u64(&mut buffer))
+    });
+}
+
+pub fn random_access_benchmark(c: &mut Criterion) {
+    // Random access with a position that has about half of its bits set.
+    let position = 0x5555_5555_5555_5555_5555_5555_5555_5555u128;
+    c.bench_function("lcg::get_state", |b| {
+        b.iter(|| lcg::get_state(LCG_M65_1, 1, 0, black_box(position)))
+    });
+    c.bench_function("PowerTable::get_state", |b| {
+        b.iter(|| LCG_M65_1_POWERS.get_state(1, 0, black_box(position)))
+    });
+    let state = lcg::get_state(LCG_M65_1, 1, 0, position);
+    c.bench_function("lcg::get_iterations", |b| {
+        b.iter(|| lcg::get_iterations(LCG_M65_1, 1, 0, black_box(state)))
+    });
+    c.bench_function("PowerTable::get_iterations", |b| {
+        b.iter(|| LCG_M65_1_POWERS.get_iterations(1, 0, black_box(state)))
+    });
+
+    let mut krull64 = Krull64::from_128(position);
+    c.bench_function("Krull64::jump", |b| {
+        b.iter(|| krull64.jump(black_box(position as i128)))
+    });
+    c.bench_function("Krull64::set_position", |b| {
+        b.iter(|| krull64.set_position(black_box(position)))
+    });
+    c.bench_function("Krull64::position", |b| {
+        b.iter(|| black_box(&krull64).position())
+    });
+    c.bench_function("Krull64::stream", |b| {
+        b.iter(|| black_box(&krull64).stream())
+    });
+    c.bench_function("Krull64::output_at", |b| {
+        b.iter(|| Krull64::output_at(black_box(1), black_box(position)))
+    });
+
+    let mut krull65 = Krull65::from_192(position, position as u64);
+    c.bench_function("Krull65::jump", |b| {
+        b.iter(|| krull65.jump(black_box(position as i128)))
+    });
+    c.bench_function("Krull65::set_position", |b| {
+        b.iter(|| krull65.set_position(black_box(position)))
+    });
+    c.bench_function("Krull65::position", |b| {
+        b.iter(|| black_box(&krull65).position())
+    });
+    c.bench_function("Krull65::stream", |b| {
+        b.iter(|| black_box(&krull65).stream())
+    });
+    c.bench_function("Krull65::output_at", |b| {
+        b.iter(|| Krull65::output_at(black_box(1), black_box(position)))
+    });
+}
+
+pub fn noise_benchmark(c: &mut Criterion) {
+    let noise = Noise::from_64(1);
+    c.bench_function("Noise::value_1d", |b| {
+        b.iter(|| noise.value_1d(black_box(1.5)))
+    });
+    c.bench_function("Noise::value_2d", |b| {
+        b.iter(|| noise.value_2d(black_box(1.5), black_box(-2.5)))
+    });
+    c.bench_function("Noise::value_3d", |b| {
+        b.iter(|| noise.value_3d(black_box(1.5), black_box(-2.5), black_box(3.5)))
+    });
+    c.bench_function("Noise::gradient_1d", |b| {
+        b.iter(|| noise.gradient_1d(black_box(1.5)))
+    });
+    c.bench_function("Noise::gradient_2d", |b| {
+        b.iter(|| noise.gradient_2d(black_box(1.5), black_box(-2.5)))
+    });
+    c.bench_function("Noise::gradient_3d", |b| {
+        b.iter(|| noise.gradient_3d(black_box(1.5), black_box(-2.5), black_box(3.5)))
+    });
+    c.bench_function("Noise::gradient_3d_f32", |b| {
+        b.iter(|| noise.gradient_3d_f32(black_box(1.5), black_box(-2.5), black_box(3.5)))
+    });
+}
+
+pub fn weighted_benchmark(c: &mut Criterion) {
+    let mut krull = Krull64::new();
+    let weights: [f64; 256] = core::array::from_fn(|_| krull.next_f64());
+    let mut cumulative = weights;
+    let index = WeightedIndex::new(&mut cumulative).unwrap();
+    c.bench_function("WeightedIndex::sample (256)", |b| {
+        b.iter(|| index.sample(&mut krull))
+    });
+    let mut probabilities = [0.0; 256];
+    let mut aliases = [0; 256];
+    let alias = WeightedAlias::new(&weights, &mut probabilities, &mut aliases).unwrap();
+    c.bench_function("WeightedAlias::sample (256)", |b| {
+        b.iter(|| alias.sample(&mut krull))
+    });
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    random_access_benchmark,
+    noise_benchmark,
+    weighted_benchmark
+);
 criterion_main!(benches);
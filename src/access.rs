@@ -0,0 +1,42 @@
+use core::fmt::Debug;
+
+// This module defines a trait for random access RNGs so that utilities
+// can be written once for both Krull64 and Krull65.
+
+/// RNG with random access inside streams. Implemented by Krull64 and Krull65.
+/// The full state of the generator is (stream, position).
+pub trait RandomAccess: Clone {
+    /// Stream number.
+    type Stream: Copy + Eq + Debug;
+
+    /// Returns the current 64-bit output.
+    fn get(&self) -> u64;
+
+    /// Generates the next 64-bit random number.
+    fn step(&mut self) -> u64;
+
+    /// Steps back to the previous position and returns the 64-bit output there.
+    fn step_back(&mut self) -> u64;
+
+    /// Jumps forward (if steps > 0) or backward (if steps < 0) or does nothing (if steps = 0).
+    /// The stream wraps around, so signed steps can be interpreted as unsigned.
+    fn jump(&mut self, steps: i128);
+
+    /// Returns current position in stream.
+    fn position(&self) -> u128;
+
+    /// Sets position in stream.
+    fn set_position(&mut self, position: u128);
+
+    /// Resets stream position to 0. Equivalent to set_position(0).
+    fn reset(&mut self);
+
+    /// Returns current stream.
+    fn stream(&self) -> Self::Stream;
+
+    /// Sets stream and initializes position to 0.
+    fn set_stream(&mut self, stream: Self::Stream);
+
+    /// Returns the 64-bit output at the given position of the given stream.
+    fn output_at(stream: Self::Stream, position: u128) -> u64;
+}
@@ -0,0 +1,172 @@
+use super::{Error, Krull64, RngCore};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// AtomicKrull64 is a Krull64 that can be shared between threads by reference.
+// Krull64 output is fully determined by (stream, position),
+// so it suffices to hand out positions with an atomic counter
+// and compute the outputs with random access.
+
+/// Krull64 RNG that can be shared between threads without locking.
+/// Each draw claims the next stream position atomically.
+/// Draws are divided between threads in an unspecified order,
+/// but the multiset of values drawn is always equal to that
+/// of a serial run of Krull64 making the same number of draws.
+/// Up to 2**64 positions can be drawn before the counter wraps around.
+#[derive(Debug)]
+pub struct AtomicKrull64 {
+    /// Stream number.
+    stream: u64,
+    /// Stream position when the counter is zero.
+    origin: u128,
+    /// Number of positions handed out since origin.
+    counter: AtomicU64,
+}
+
+impl AtomicKrull64 {
+    /// Creates a new AtomicKrull64 RNG.
+    /// Stream and position are set to 0.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        AtomicKrull64::from_64(0)
+    }
+
+    /// Creates a new AtomicKrull64 RNG from a 64-bit seed.
+    /// Stream is set to the given seed and position is set to 0.
+    /// All seeds work equally well.
+    pub fn from_64(seed: u64) -> Self {
+        AtomicKrull64 {
+            stream: seed,
+            origin: 0,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a new AtomicKrull64 RNG that continues from
+    /// the stream and position of a Krull64 RNG.
+    pub fn from_krull64(krull: &Krull64) -> Self {
+        AtomicKrull64 {
+            stream: krull.stream(),
+            origin: krull.position(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns a Krull64 RNG with the current stream and position.
+    pub fn to_krull64(&self) -> Krull64 {
+        let mut krull = Krull64::from_64(self.stream);
+        krull.set_position(self.position());
+        krull
+    }
+
+    /// Returns stream.
+    #[inline]
+    pub fn stream(&self) -> u64 {
+        self.stream
+    }
+
+    /// Returns current position in stream, that is,
+    /// the position of the latest output handed out.
+    #[inline]
+    pub fn position(&self) -> u128 {
+        self.origin
+            .wrapping_add(self.counter.load(Ordering::Relaxed) as u128)
+    }
+
+    /// Claims the next `n` positions. Returns the position preceding them.
+    #[inline]
+    fn claim(&self, n: u64) -> u128 {
+        // Only the counter itself needs to be consistent, so relaxed ordering suffices.
+        self.origin
+            .wrapping_add(self.counter.fetch_add(n, Ordering::Relaxed) as u128)
+    }
+
+    /// Generates the next 64-bit random number.
+    #[inline]
+    pub fn step(&self) -> u64 {
+        Krull64::output_at(self.stream, self.claim(1).wrapping_add(1))
+    }
+}
+
+impl From<Krull64> for AtomicKrull64 {
+    fn from(krull: Krull64) -> Self {
+        AtomicKrull64::from_krull64(&krull)
+    }
+}
+
+impl RngCore for &AtomicKrull64 {
+    fn next_u32(&mut self) -> u32 {
+        self.step() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.step()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        // Claim a contiguous range of positions for the whole buffer
+        // so it can be filled with a serial generator.
+        let steps = (dest.len() as u64 + 7) >> 3;
+        if steps == 0 {
+            return;
+        }
+        let mut krull = Krull64::from_64(self.stream);
+        krull.set_position(self.claim(steps));
+        krull.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    pub fn run_tests() {
+        // Single-threaded use reproduces the serial sequence.
+        let mut krull = Krull64::from_64(1);
+        krull.set_position(0xffff_ffff_ffff_fff0);
+        let atomic = AtomicKrull64::from_krull64(&krull);
+        assert_eq!(krull, atomic.to_krull64());
+        for _ in 0..0x40 {
+            assert_eq!(krull.next_u64(), (&atomic).next_u64());
+        }
+        assert_eq!(krull.position(), atomic.position());
+        let mut buffer1 = [0u8; 0x55];
+        let mut buffer2 = [0u8; 0x55];
+        krull.fill_bytes(&mut buffer1);
+        (&atomic).fill_bytes(&mut buffer2);
+        assert_eq!(buffer1, buffer2);
+        assert_eq!(krull, atomic.to_krull64());
+
+        // Multi-threaded draws equal the serial draws as a multiset.
+        let threads = 4;
+        let draws = 0x1000;
+        let atomic = AtomicKrull64::from_64(2);
+        let mut values: Vec<u64> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut rng = &atomic;
+                        (0..draws).map(|_| rng.next_u64()).collect::<Vec<u64>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        let mut krull = Krull64::from_64(2);
+        let mut expected: Vec<u64> = (0..threads * draws).map(|_| krull.next_u64()).collect();
+        values.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(expected, values);
+        assert_eq!(krull.position(), atomic.position());
+    }
+}
@@ -0,0 +1,378 @@
+use super::{Error, RandomAccess, RngCore};
+use core::num::NonZeroU32;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// A block is a contiguous range of positions in a stream. Generators bounded
+// to disjoint blocks are guaranteed never to produce overlapping outputs.
+
+/// Error code reported by `try_fill_bytes` when a bounded generator runs out of outputs.
+pub const EXHAUSTED: u32 = Error::CUSTOM_START;
+
+/// Returns the error reported when a bounded generator runs out of outputs.
+fn exhausted() -> Error {
+    Error::from(NonZeroU32::new(EXHAUSTED).unwrap())
+}
+
+/// What a bounded generator does when it runs out of outputs.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Overrun {
+    /// Panic.
+    Panic,
+    /// Wrap around to the start of the block.
+    Wrap,
+    /// Return an error with code `EXHAUSTED` from `try_fill_bytes`.
+    /// Infallible methods panic.
+    Error,
+}
+
+/// Random access RNG bounded to a block of positions in its stream.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Block<R: RandomAccess> {
+    /// Generator at the position of the latest output.
+    rng: R,
+    /// Start of the block: the position preceding the first output.
+    start: u128,
+    /// Number of outputs in the block.
+    len: u128,
+    /// Number of outputs consumed.
+    consumed: u128,
+    /// Policy when outputs run out.
+    overrun: Overrun,
+}
+
+impl<R: RandomAccess> Block<R> {
+    /// Creates a generator bounded to the `len` outputs following position `start`
+    /// in the stream of `rng`. The generator itself is not affected.
+    pub fn new(rng: &R, start: u128, len: u128, overrun: Overrun) -> Self {
+        let mut rng = rng.clone();
+        rng.set_position(start);
+        Block {
+            rng,
+            start,
+            len,
+            consumed: 0,
+            overrun,
+        }
+    }
+
+    /// Reserves the next `n` outputs of `rng` and advances `rng` past them.
+    /// Returns a generator with a budget of `n` outputs that reports an error
+    /// from `try_fill_bytes` when exhausted; infallible methods panic instead.
+    pub fn reserve(rng: &mut R, n: u128) -> Budgeted<R> {
+        let block = Block {
+            rng: rng.clone(),
+            start: rng.position(),
+            len: n,
+            consumed: 0,
+            overrun: Overrun::Error,
+        };
+        rng.jump(n as i128);
+        block
+    }
+
+    /// Returns the start of the block: the position preceding the first output.
+    #[inline]
+    pub fn start(&self) -> u128 {
+        self.start
+    }
+
+    /// Returns the number of outputs in the block.
+    #[inline]
+    pub fn len(&self) -> u128 {
+        self.len
+    }
+
+    /// Returns whether the block has no outputs.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of outputs consumed. After a wrap around, counting starts from 0.
+    #[inline]
+    pub fn consumed(&self) -> u128 {
+        self.consumed
+    }
+
+    /// Returns the number of outputs remaining.
+    #[inline]
+    pub fn remaining(&self) -> u128 {
+        self.len - self.consumed
+    }
+
+    /// Returns the overrun policy.
+    #[inline]
+    pub fn overrun(&self) -> Overrun {
+        self.overrun
+    }
+
+    /// Returns the underlying generator at the position of the latest output.
+    #[inline]
+    pub fn inner(&self) -> &R {
+        &self.rng
+    }
+
+    /// Rewinds to the start of the block.
+    pub fn rewind(&mut self) {
+        // The stream wraps around, so the negation may wrap too: 2**127 outputs can be consumed.
+        self.rng.jump((self.consumed as i128).wrapping_neg());
+        self.consumed = 0;
+    }
+
+    /// Generates the next 64-bit random number. When outputs run out,
+    /// wraps around or panics depending on the overrun policy.
+    #[inline]
+    pub fn step(&mut self) -> u64 {
+        if self.consumed == self.len {
+            if self.overrun != Overrun::Wrap || self.len == 0 {
+                panic!("Block::step: block exhausted");
+            }
+            self.rewind();
+        }
+        self.consumed += 1;
+        self.rng.step()
+    }
+}
+
+impl<R: RandomAccess + RngCore> RngCore for Block<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.step() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.step()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let steps = ((dest.len() as u128) + 7) >> 3;
+        if steps <= self.remaining() {
+            self.consumed += steps;
+            self.rng.fill_bytes(dest);
+            return;
+        }
+        let mut chunks = dest.chunks_exact_mut(8);
+        // Always use Little-Endian.
+        for chunk in &mut chunks {
+            chunk.copy_from_slice(&self.step().to_le_bytes());
+        }
+        let rest = chunks.into_remainder();
+        if !rest.is_empty() {
+            let x = self.step();
+            rest.copy_from_slice(&x.to_le_bytes()[0..rest.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        // Fail without consuming anything.
+        if self.overrun == Overrun::Error && ((dest.len() as u128) + 7) >> 3 > self.remaining() {
+            return Err(exhausted());
+        }
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Random access RNG with a consumption budget. Created with `reserve`,
+/// it owns a fixed slice of a stream and reports an error from `try_fill_bytes`
+/// when the budget is exhausted.
+pub type Budgeted<R> = Block<R>;
+
+/// Iterator over equal, disjoint blocks of a stream.
+/// Block i consists of the outputs of calls number i * len + 1 to (i + 1) * len
+/// of `step()`, counted from the starting position.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Partition<R: RandomAccess> {
+    /// Generator in the partitioned stream.
+    rng: R,
+    /// Starting position.
+    start: u128,
+    /// Number of outputs in each block.
+    block_len: u128,
+    /// Index of next block.
+    next: u128,
+    /// Number of blocks.
+    blocks: u128,
+    /// Overrun policy of blocks.
+    overrun: Overrun,
+}
+
+impl<R: RandomAccess> Partition<R> {
+    /// Partitions the stream of `rng`, starting from its current position, into `n` equal blocks.
+    /// The blocks are as long as possible: each block has floor(2**128 / n) outputs,
+    /// except that a single block has 2**128 - 1 outputs. Panics if n is zero.
+    pub fn new(rng: &R, n: u128, overrun: Overrun) -> Self {
+        assert!(n > 0, "Partition::new: number of blocks must be positive");
+        // Compute floor(2**128 / n) from floor((2**128 - 1) / n).
+        let block_len = if n == 1 {
+            u128::MAX
+        } else {
+            u128::MAX / n + (u128::MAX % n == n - 1) as u128
+        };
+        Partition {
+            rng: rng.clone(),
+            start: rng.position(),
+            block_len,
+            next: 0,
+            blocks: n,
+            overrun,
+        }
+    }
+
+    /// Returns the number of outputs in each block.
+    #[inline]
+    pub fn block_len(&self) -> u128 {
+        self.block_len
+    }
+
+    /// Returns block i. Panics if i is out of range.
+    pub fn block(&self, i: u128) -> Block<R> {
+        assert!(i < self.blocks, "Partition::block: index out of range");
+        Block::new(
+            &self.rng,
+            self.start.wrapping_add(i * self.block_len),
+            self.block_len,
+            self.overrun,
+        )
+    }
+}
+
+impl<R: RandomAccess> Iterator for Partition<R> {
+    type Item = Block<R>;
+
+    fn next(&mut self) -> Option<Block<R>> {
+        if self.next < self.blocks {
+            self.next += 1;
+            Some(self.block(self.next - 1))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.blocks - self.next) {
+            Ok(n) => (n, Some(n)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Block<R>> {
+        self.next = self.next.saturating_add(n as u128).min(self.blocks);
+        self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    fn test_partition<R: RandomAccess + RngCore>(mut rng: R) {
+        rng.set_position(1 << 80);
+        let mut serial = rng.clone();
+        let mut blocks = [(); 4].map(|_| None);
+        for (i, block) in Partition::new(&rng, 1 << 124, Overrun::Error)
+            .take(4)
+            .enumerate()
+        {
+            assert_eq!(16, block.len());
+            blocks[i] = Some(block);
+        }
+        for block in blocks.iter_mut() {
+            let block = block.as_mut().unwrap();
+            let mut buffer1 = [0u8; 0x78];
+            let mut buffer2 = [0u8; 0x78];
+            block.fill_bytes(&mut buffer1[0..0x40]);
+            serial.fill_bytes(&mut buffer2[0..0x40]);
+            assert_eq!(buffer1, buffer2);
+            assert_eq!(8, block.consumed());
+            assert_eq!(8, block.remaining());
+            assert!(block.try_fill_bytes(&mut buffer1[0..0x41]).is_err());
+            assert_eq!(8, block.remaining());
+            assert!(block.try_fill_bytes(&mut buffer1[0..0x38]).is_ok());
+            serial.fill_bytes(&mut buffer2[0..0x38]);
+            assert_eq!(buffer1, buffer2);
+            assert_eq!(block.next_u64(), serial.next_u64());
+            assert_eq!(0, block.remaining());
+            assert_eq!(
+                Some(EXHAUSTED),
+                block
+                    .try_fill_bytes(&mut buffer1[0..1])
+                    .err()
+                    .and_then(|error| error.code())
+                    .map(u32::from)
+            );
+            assert_eq!(serial.position(), block.inner().position());
+        }
+
+        // Wrap around.
+        let mut block = Partition::new(&rng, 3, Overrun::Wrap).nth(2).unwrap();
+        assert_eq!(u128::MAX / 3, block.len());
+        assert_eq!(rng.position() + 2 * block.len(), block.start());
+        let first = block.next_u64();
+        block.rng.jump((block.len() - 2) as i128);
+        block.consumed = block.len() - 1;
+        assert_eq!(
+            R::output_at(rng.stream(), rng.position().wrapping_add(3 * block.len())),
+            block.next_u64()
+        );
+        assert_eq!(first, block.next_u64());
+        assert_eq!(1, block.consumed());
+
+        // Wrap around a block of 2**127 outputs.
+        let mut block = Partition::new(&rng, 2, Overrun::Wrap).next().unwrap();
+        assert_eq!(1 << 127, block.len());
+        let first = block.next_u64();
+        block.rng.jump((block.len() - 1) as i128);
+        block.consumed = block.len();
+        assert_eq!(first, block.next_u64());
+        assert_eq!(1, block.consumed());
+
+        let partition = Partition::new(&rng, 1, Overrun::Panic);
+        assert_eq!(u128::MAX, partition.block_len());
+        assert_eq!(
+            1 << 127,
+            Partition::new(&rng, 2, Overrun::Panic).block_len()
+        );
+        assert!(Partition::new(&rng, 2, Overrun::Panic).nth(2).is_none());
+    }
+
+    #[test]
+    pub fn run_tests() {
+        test_partition(Krull64::from_64(1));
+        test_partition(Krull65::from_128(1));
+        let mut block = Krull64::new().partition(2, Overrun::Panic).next().unwrap();
+        assert_eq!(Krull64::output_at(0, 1), block.step());
+    }
+
+    #[test]
+    pub fn budget_tests() {
+        let mut krull = Krull65::from_64(1);
+        let mut subsystem1 = krull.reserve(3);
+        let mut subsystem2 = krull.reserve(5);
+        assert_eq!(5, krull.position() - subsystem2.start());
+        assert_eq!(8, krull.position());
+        assert_eq!(Krull65::output_at(1, 9), krull.next_u64());
+        for i in 1..=3 {
+            assert_eq!(Krull65::output_at(1, i), subsystem1.next_u64());
+            assert_eq!(i, subsystem1.consumed());
+        }
+        let mut buffer = [0u8; 0x30];
+        assert!(subsystem1.try_fill_bytes(&mut buffer[0..1]).is_err());
+        assert!(subsystem2.try_fill_bytes(&mut buffer[0..0x29]).is_err());
+        assert_eq!(0, subsystem2.consumed());
+        assert!(subsystem2.try_fill_bytes(&mut buffer[0..0x28]).is_ok());
+        assert_eq!(0, subsystem2.remaining());
+        assert_eq!(Krull65::output_at(1, 8).to_le_bytes(), buffer[0x20..0x28]);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn panic_on_overrun() {
+        let mut block = Block::new(&Krull64::new(), 0, 1, Overrun::Panic);
+        block.step();
+        block.step();
+    }
+}
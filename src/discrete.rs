@@ -0,0 +1,779 @@
+use super::math::{bd0, exp, exp_m1, floor, ln, ln_1p, sqrt, stirling_error};
+use super::{Distribution, Float, Gamma, RngCore};
+use core::f64::consts::TAU;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Discrete distributions for any RNG. Probabilities are computed with the saddle point
+// expansions of Loader, C., Fast and Accurate Computation of Binomial Probabilities (2000),
+// which stay accurate for large parameters, and elementary functions from our own math module,
+// so results are identical on all platforms. The algorithms, and thus the values sampled
+// and the number of outputs consumed, are frozen within a crate version.
+// Distributions whose outcome is certain consume no outputs.
+
+/// Largest mean of the Poisson distribution, 2**62.
+const MAX_LAMBDA: f64 = 4611686018427387904.0;
+
+/// Returns the natural logarithm of the Poisson probability of k with mean lambda.
+fn ln_poisson_pmf(k: u64, lambda: f64) -> f64 {
+    if k == 0 {
+        -lambda
+    } else {
+        let x = k as f64;
+        -stirling_error(k) - bd0(x, lambda) - 0.5 * ln(TAU * x)
+    }
+}
+
+/// Returns the natural logarithm of the binomial probability of k successes in n trials
+/// with success probability p and failure probability q = 1 - p.
+fn ln_binomial_pmf(k: u64, n: u64, p: f64, q: f64) -> f64 {
+    if k == 0 {
+        if n == 0 {
+            0.0
+        } else {
+            n as f64 * ln_1p(-p)
+        }
+    } else if k == n {
+        n as f64 * ln_1p(-q)
+    } else {
+        let (x, y, m) = (k as f64, (n - k) as f64, n as f64);
+        stirling_error(n)
+            - stirling_error(k)
+            - stirling_error(n - k)
+            - bd0(x, m * p)
+            - bd0(y, m * q)
+            + 0.5 * ln(m / (TAU * x * y))
+    }
+}
+
+/// Samples the Poisson distribution with mean lambda in (0, MAX_LAMBDA].
+fn poisson<R: RngCore + ?Sized>(rng: &mut R, lambda: f64) -> u64 {
+    if lambda < 10.0 {
+        // Inversion by sequential search. Rounding can leave the total probability
+        // slightly below 1, in which case we try again.
+        loop {
+            let u = rng.next_f64();
+            let mut k = 0;
+            let mut p = exp(-lambda);
+            let mut sum = p;
+            while u >= sum && p > 0.0 {
+                k += 1;
+                p *= lambda / k as f64;
+                sum += p;
+            }
+            if u < sum {
+                return k;
+            }
+        }
+    }
+    // Hormann, W., The Transformed Rejection Method for Generating Poisson Random Variables (1993).
+    let b = 0.931 + 2.53 * sqrt(lambda);
+    let a = -0.059 + 0.02483 * b;
+    let ln_inv_alpha = ln(1.1239 + 1.1328 / (b - 3.4));
+    let v_r = 0.9277 - 3.6224 / (b - 2.0);
+    loop {
+        let u = rng.next_f64() - 0.5;
+        let v = rng.next_f64();
+        let us = 0.5 - u.abs();
+        let k = floor((2.0 * a / us + b) * u + lambda + 0.43);
+        if us >= 0.07 && v <= v_r {
+            return k as u64;
+        }
+        if k < 0.0 || (us < 0.013 && v > us) {
+            continue;
+        }
+        let k = k as u64;
+        if ln(v) + ln_inv_alpha - ln(a / (us * us) + b) <= ln_poisson_pmf(k, lambda) {
+            return k;
+        }
+    }
+}
+
+/// Poisson distribution: the number of events in an interval when events occur
+/// independently at a constant mean rate.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Poisson {
+    lambda: f64,
+}
+
+impl Poisson {
+    /// Creates a Poisson distribution with mean lambda.
+    /// Returns `None` unless lambda is positive and at most 2**62.
+    pub fn new(lambda: f64) -> Option<Self> {
+        if lambda > 0.0 && lambda <= MAX_LAMBDA {
+            Some(Poisson { lambda })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the mean.
+    #[inline]
+    pub fn lambda(&self) -> f64 {
+        self.lambda
+    }
+}
+
+impl Distribution<u64> for Poisson {
+    /// Samples by inversion if lambda < 10, consuming one output, and more only in the rare
+    /// case that rounding leaves probability unaccounted for. Otherwise uses the PTRS method,
+    /// which consumes two outputs per iteration and needs fewer than 1.15 iterations on average.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> u64 {
+        poisson(rng, self.lambda)
+    }
+}
+
+/// Binomial distribution: the number of successes in n independent trials.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Binomial {
+    n: u64,
+    p: f64,
+}
+
+impl Binomial {
+    /// Creates a binomial distribution with n trials and success probability p.
+    /// Returns `None` unless p is in [0, 1].
+    pub fn new(n: u64, p: f64) -> Option<Self> {
+        if (0.0..=1.0).contains(&p) {
+            Some(Binomial { n, p })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of trials.
+    #[inline]
+    pub fn n(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns the success probability.
+    #[inline]
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+
+    /// Samples by inversion, consuming one output, and more only in the rare case
+    /// that the search exceeds its bound.
+    fn sample_inversion<R: RngCore + ?Sized>(&self, rng: &mut R, r: f64) -> u64 {
+        let q = 1.0 - r;
+        let mean = self.n as f64 * r;
+        let p0 = exp(self.n as f64 * ln_1p(-r));
+        let bound = (mean + 10.0 * sqrt(mean * q + 1.0)).min(self.n as f64) as u64;
+        loop {
+            let mut u = rng.next_f64();
+            let mut k = 0;
+            let mut p = p0;
+            while u >= p && k < bound {
+                u -= p;
+                k += 1;
+                p *= (self.n - k + 1) as f64 * r / (k as f64 * q);
+            }
+            if u < p {
+                return k;
+            }
+        }
+    }
+
+    /// Samples with the BTPE method, which consumes two outputs per iteration.
+    fn sample_btpe<R: RngCore + ?Sized>(&self, rng: &mut R, r: f64) -> u64 {
+        // Kachitvichyanukul, V. and Schmeiser, B. W., Binomial Random Variate Generation (1988).
+        // The final acceptance test evaluates the probabilities exactly.
+        let q = 1.0 - r;
+        let n = self.n as f64;
+        let npq = n * r * q;
+        let f_m = n * r + r;
+        let m = floor(f_m);
+        let p1 = floor(2.195 * sqrt(npq) - 4.6 * q) + 0.5;
+        let x_m = m + 0.5;
+        let x_l = x_m - p1;
+        let x_r = x_m + p1;
+        let c = 0.134 + 20.5 / (15.3 + m);
+        let a = (f_m - x_l) / (f_m - x_l * r);
+        let lambda_l = a * (1.0 + 0.5 * a);
+        let a = (x_r - f_m) / (x_r * q);
+        let lambda_r = a * (1.0 + 0.5 * a);
+        let p2 = p1 * (1.0 + 2.0 * c);
+        let p3 = p2 + c / lambda_l;
+        let p4 = p3 + c / lambda_r;
+        loop {
+            let u = rng.next_f64() * p4;
+            let mut v = rng.next_f64_open();
+            let y = if u <= p1 {
+                // Triangular region, accepted immediately.
+                return floor(x_m - p1 * v + u) as u64;
+            } else if u <= p2 {
+                // Parallelograms.
+                let x = x_l + (u - p1) / c;
+                v = v * c + 1.0 - (m - x + 0.5).abs() / p1;
+                if v > 1.0 {
+                    continue;
+                }
+                floor(x)
+            } else if u <= p3 {
+                // Left exponential tail.
+                let y = floor(x_l + ln(v) / lambda_l);
+                if y < 0.0 {
+                    continue;
+                }
+                v *= (u - p2) * lambda_l;
+                y
+            } else {
+                // Right exponential tail.
+                let y = floor(x_r - ln(v) / lambda_r);
+                if y > n {
+                    continue;
+                }
+                v *= (u - p3) * lambda_r;
+                y
+            };
+            let k = (y - m).abs();
+            if k <= 20.0 {
+                // Evaluate the probability ratio f(y) / f(m) recursively.
+                let s = r / q;
+                let a = s * (n + 1.0);
+                let mut f = 1.0;
+                let mut i = m + 1.0;
+                while i <= y {
+                    f *= a / i - s;
+                    i += 1.0;
+                }
+                let mut i = y + 1.0;
+                while i <= m {
+                    f /= a / i - s;
+                    i += 1.0;
+                }
+                if v <= f {
+                    return y as u64;
+                }
+                continue;
+            }
+            let ln_v = ln(v);
+            if k < 0.5 * npq - 1.0 {
+                // Squeeze with bounds of the normal approximation.
+                let rho = (k / npq) * ((k * (k / 3.0 + 0.625) + 1.0 / 6.0) / npq + 0.5);
+                let t = -k * k / (2.0 * npq);
+                if ln_v < t - rho {
+                    return y as u64;
+                }
+                if ln_v > t + rho {
+                    continue;
+                }
+            }
+            let (y, m) = (y as u64, m as u64);
+            if ln_v <= ln_binomial_pmf(y, self.n, r, q) - ln_binomial_pmf(m, self.n, r, q) {
+                return y;
+            }
+        }
+    }
+}
+
+impl Distribution<u64> for Binomial {
+    /// Samples by inversion if n * min(p, 1 - p) < 30, consuming one output, and more only
+    /// in rare cases. Otherwise uses the BTPE method, which consumes two outputs
+    /// per iteration and needs fewer than 1.3 iterations on average.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> u64 {
+        if self.n == 0 || self.p == 0.0 || self.p == 1.0 {
+            return if self.p == 1.0 { self.n } else { 0 };
+        }
+        // Sample the number of the less likely outcomes.
+        let r = self.p.min(1.0 - self.p);
+        let k = if self.n as f64 * r < 30.0 {
+            self.sample_inversion(rng, r)
+        } else {
+            self.sample_btpe(rng, r).min(self.n)
+        };
+        if self.p > 0.5 {
+            self.n - k
+        } else {
+            k
+        }
+    }
+}
+
+/// Geometric distribution: the number of failures before the first success
+/// in independent trials.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Geometric {
+    p: f64,
+    /// Logarithm of the failure probability.
+    ln_q: f64,
+}
+
+impl Geometric {
+    /// Creates a geometric distribution with success probability p.
+    /// Returns `None` unless p is in (0, 1].
+    pub fn new(p: f64) -> Option<Self> {
+        if p > 0.0 && p <= 1.0 {
+            Some(Geometric { p, ln_q: ln_1p(-p) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the success probability.
+    #[inline]
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+}
+
+impl Distribution<u64> for Geometric {
+    /// Samples by inversion, consuming one output if p < 1.
+    /// Results saturate at `u64::MAX`, which matters only for p below 2**-58 or so.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> u64 {
+        if self.p == 1.0 {
+            return 0;
+        }
+        // The conversion truncates towards zero and saturates.
+        (ln(rng.next_f64_open()) / self.ln_q) as u64
+    }
+}
+
+/// Negative binomial distribution: the number of failures before the rth success
+/// in independent trials.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NegativeBinomial {
+    r: f64,
+    p: f64,
+    /// Gamma distribution of the Poisson mean, or `None` if p = 1.
+    gamma: Option<Gamma>,
+}
+
+impl NegativeBinomial {
+    /// Creates a negative binomial distribution with r successes, which need not be
+    /// an integer, and success probability p.
+    /// Returns `None` unless r is finite and positive and p is in (0, 1].
+    pub fn new(r: f64, p: f64) -> Option<Self> {
+        if !(r.is_finite() && r > 0.0 && p > 0.0 && p <= 1.0) {
+            return None;
+        }
+        let gamma = if p < 1.0 {
+            Some(Gamma::new(r, (1.0 - p) / p)?)
+        } else {
+            None
+        };
+        Some(NegativeBinomial { r, p, gamma })
+    }
+
+    /// Returns the number of successes.
+    #[inline]
+    pub fn r(&self) -> f64 {
+        self.r
+    }
+
+    /// Returns the success probability.
+    #[inline]
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+}
+
+impl Distribution<u64> for NegativeBinomial {
+    /// Samples a Poisson distribution whose mean is drawn from a gamma distribution,
+    /// consuming the outputs of both. Means beyond 2**62 saturate.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> u64 {
+        match &self.gamma {
+            None => 0,
+            Some(gamma) => {
+                let lambda = gamma.sample(rng).min(MAX_LAMBDA);
+                if lambda > 0.0 {
+                    poisson(rng, lambda)
+                } else {
+                    0
+                }
+            }
+        }
+    }
+}
+
+/// Hypergeometric distribution: the number of successes in draws without replacement
+/// from a population that contains a given number of successes.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hypergeometric {
+    total: u64,
+    successes: u64,
+    draws: u64,
+    /// Support of the distribution.
+    min: u64,
+    max: u64,
+    /// Mode of the distribution.
+    mode: u64,
+    method: HypergeometricMethod,
+}
+
+/// Sampling method of the hypergeometric distribution, chosen by the standard deviation.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum HypergeometricMethod {
+    /// Inversion with a search that starts from the mode, which has probability `p_mode`.
+    Inversion { p_mode: f64 },
+    /// Rejection from a hat.
+    Rejection(HypergeometricHat),
+}
+
+/// Hat of the hypergeometric rejection sampler, relative to the probability of the mode.
+/// It is flat in [mode - d, mode + d], and at distance j beyond that its logarithm
+/// is ln_left - lambda_left * j on the left and ln_right - lambda_right * j on the right.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct HypergeometricHat {
+    d: u64,
+    ln_mode: f64,
+    ln_left: f64,
+    lambda_left: f64,
+    ln_right: f64,
+    lambda_right: f64,
+    /// Areas of the flat part, the flat part and the left tail, and the whole hat.
+    p1: f64,
+    p2: f64,
+    p3: f64,
+}
+
+impl Hypergeometric {
+    /// Creates a hypergeometric distribution of `draws` draws from a population of `total`
+    /// items, of which `successes` are successes. Returns `None` if `successes` or `draws`
+    /// exceeds `total`.
+    pub fn new(total: u64, successes: u64, draws: u64) -> Option<Self> {
+        if successes > total || draws > total {
+            return None;
+        }
+        let min = draws.saturating_sub(total - successes);
+        let max = draws.min(successes);
+        let mode = ((draws as u128 + 1) * (successes as u128 + 1) / (total as u128 + 2)) as u64;
+        let mode = mode.clamp(min, max);
+        let mut hypergeometric = Hypergeometric {
+            total,
+            successes,
+            draws,
+            min,
+            max,
+            mode,
+            method: HypergeometricMethod::Inversion { p_mode: 1.0 },
+        };
+        if min == max {
+            return Some(hypergeometric);
+        }
+        let (n, k, m) = (total as f64, successes as f64, draws as f64);
+        let variance = m * (k / n) * ((n - k) / n) * ((n - m) / (n - 1.0));
+        hypergeometric.method = if variance < 100.0 {
+            HypergeometricMethod::Inversion {
+                p_mode: exp(hypergeometric.ln_pmf(mode)),
+            }
+        } else {
+            // The support extends more than 10 standard deviations from the mean
+            // in both directions, so the tails of the hat start inside it.
+            let d = floor(1.5 * sqrt(variance) + 0.5) as u64;
+            let (left, right) = (mode - d, mode + d);
+            let ln_mode = hypergeometric.ln_pmf(mode);
+            let ln_left = hypergeometric.ln_pmf(left) - ln_mode;
+            let ln_right = hypergeometric.ln_pmf(right) - ln_mode;
+            // The distribution is log-concave, so the probability ratios of neighbours
+            // at the ends of the flat part bound the decay of the tails.
+            let lambda_left = -ln(hypergeometric.ratio_down(left));
+            let lambda_right = -ln(hypergeometric.ratio_up(right));
+            let p1 = (2 * d + 1) as f64;
+            let p2 = p1 + exp(ln_left) / exp_m1(lambda_left);
+            let p3 = p2 + exp(ln_right) / exp_m1(lambda_right);
+            HypergeometricMethod::Rejection(HypergeometricHat {
+                d,
+                ln_mode,
+                ln_left,
+                lambda_left,
+                ln_right,
+                lambda_right,
+                p1,
+                p2,
+                p3,
+            })
+        };
+        Some(hypergeometric)
+    }
+
+    /// Returns the size of the population.
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the number of successes in the population.
+    #[inline]
+    pub fn successes(&self) -> u64 {
+        self.successes
+    }
+
+    /// Returns the number of draws.
+    #[inline]
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+
+    /// Returns the natural logarithm of the probability of k, where min <= k <= max
+    /// and 0 < draws < total.
+    fn ln_pmf(&self, k: u64) -> f64 {
+        // The probability is a ratio of binomial probabilities, which are accurate
+        // for any parameters with p = draws / total.
+        let p = self.draws as f64 / self.total as f64;
+        let q = (self.total - self.draws) as f64 / self.total as f64;
+        ln_binomial_pmf(k, self.successes, p, q)
+            + ln_binomial_pmf(self.draws - k, self.total - self.successes, p, q)
+            - ln_binomial_pmf(self.draws, self.total, p, q)
+    }
+
+    /// Returns f(k - 1) / f(k) = k (N - K - n + k) / ((K - k + 1) (n - k + 1)).
+    fn ratio_down(&self, k: u64) -> f64 {
+        let (n, s, d, k) = (
+            self.total as f64,
+            self.successes as f64,
+            self.draws as f64,
+            k as f64,
+        );
+        k * (n - s - d + k) / ((s - k + 1.0) * (d - k + 1.0))
+    }
+
+    /// Returns f(k + 1) / f(k) = (K - k) (n - k) / ((k + 1) (N - K - n + k + 1)).
+    fn ratio_up(&self, k: u64) -> f64 {
+        let (n, s, d, k) = (
+            self.total as f64,
+            self.successes as f64,
+            self.draws as f64,
+            k as f64,
+        );
+        (s - k) * (d - k) / ((k + 1.0) * (n - s - d + k + 1.0))
+    }
+
+    /// Samples by inversion with a search that starts from the mode.
+    fn sample_inversion<R: RngCore + ?Sized>(&self, rng: &mut R, p_mode: f64) -> u64 {
+        loop {
+            let mut u = rng.next_f64() - p_mode;
+            let (mut low, mut high) = (self.mode, self.mode);
+            let (mut p_low, mut p_high) = (p_mode, p_mode);
+            while u >= 0.0 && (p_low > 0.0 || p_high > 0.0) {
+                if low > self.min {
+                    p_low *= self.ratio_down(low);
+                    low -= 1;
+                    u -= p_low;
+                    if u < 0.0 {
+                        return low;
+                    }
+                } else {
+                    p_low = 0.0;
+                }
+                if high < self.max {
+                    p_high *= self.ratio_up(high);
+                    high += 1;
+                    u -= p_high;
+                } else {
+                    p_high = 0.0;
+                }
+            }
+            if u < 0.0 {
+                return high;
+            }
+        }
+    }
+
+    /// Samples by rejection, consuming two outputs per iteration.
+    fn sample_rejection<R: RngCore + ?Sized>(&self, rng: &mut R, hat: &HypergeometricHat) -> u64 {
+        // Kachitvichyanukul, V. and Schmeiser, B. W., Computer Generation of Hypergeometric
+        // Random Variates (1985). Our tails are geometric instead of exponential,
+        // fitted at integer points, and the acceptance test evaluates the probabilities exactly.
+        let (left, right) = (self.mode - hat.d, self.mode + hat.d);
+        loop {
+            let u = rng.next_f64() * hat.p3;
+            let v = rng.next_f64_open();
+            // Pick k from the hat and the logarithm of a uniform number times the hat at k.
+            let (k, ln_w) = if u < hat.p1 {
+                (left + u as u64, ln(v))
+            } else if u < hat.p2 {
+                // Left tail at distance j. The position within the region is uniform.
+                let j = 1.0 + floor(-ln(v) / hat.lambda_left);
+                if j > (left - self.min) as f64 {
+                    continue;
+                }
+                let w = (u - hat.p1) / (hat.p2 - hat.p1);
+                (left - j as u64, ln(w) + hat.ln_left - hat.lambda_left * j)
+            } else {
+                let j = 1.0 + floor(-ln(v) / hat.lambda_right);
+                if j > (self.max - right) as f64 {
+                    continue;
+                }
+                let w = (u - hat.p2) / (hat.p3 - hat.p2);
+                (
+                    right + j as u64,
+                    ln(w) + hat.ln_right - hat.lambda_right * j,
+                )
+            };
+            if ln_w <= self.ln_pmf(k) - hat.ln_mode {
+                return k;
+            }
+        }
+    }
+}
+
+impl Distribution<u64> for Hypergeometric {
+    /// If the standard deviation is less than 10, samples by inversion with a search
+    /// that starts from the mode, consuming one output, and more only in the rare case
+    /// that rounding leaves probability unaccounted for. Otherwise uses a variant of the
+    /// H2PE method, which consumes two outputs per iteration and needs fewer than
+    /// 1.5 iterations on average.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> u64 {
+        match self.method {
+            _ if self.min == self.max => self.min,
+            HypergeometricMethod::Inversion { p_mode } => self.sample_inversion(rng, p_mode),
+            HypergeometricMethod::Rejection(ref hat) => self.sample_rejection(rng, hat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    /// Returns the mean and variance of n samples.
+    fn moments<R: RngCore>(rng: &mut R, n: usize, dist: &impl Distribution<u64>) -> (f64, f64) {
+        // Accumulate offsets from the first sample to avoid cancellation with large values.
+        let first = dist.sample(rng);
+        let mut sum = 0.0;
+        let mut sum2 = 0.0;
+        for _ in 1..n {
+            let x = dist.sample(rng) as f64 - first as f64;
+            sum += x;
+            sum2 += x * x;
+        }
+        let mean = sum / n as f64;
+        (first as f64 + mean, sum2 / n as f64 - mean * mean)
+    }
+
+    /// Checks that the mean and variance of n samples are close to the given values.
+    fn check_moments<R: RngCore>(
+        rng: &mut R,
+        n: usize,
+        dist: &impl Distribution<u64>,
+        mean0: f64,
+        var0: f64,
+    ) {
+        let (mean, var) = moments(rng, n, dist);
+        // Allow 5 standard errors in the mean.
+        assert!(
+            (mean - mean0).abs() < 5.0 * sqrt(var0 / n as f64) + 1.0e-9,
+            "mean {} != {}",
+            mean,
+            mean0
+        );
+        assert!(
+            (var / var0 - 1.0).abs() < 0.1,
+            "variance {} != {}",
+            var,
+            var0
+        );
+    }
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers. These must not change within a crate version.
+        let mut krull = Krull64::from_64(0);
+        assert_eq!(3, Poisson::new(3.5).unwrap().sample(&mut krull));
+        assert_eq!(957, Poisson::new(1000.0).unwrap().sample(&mut krull));
+        assert_eq!(10, Binomial::new(20, 0.3).unwrap().sample(&mut krull));
+        assert_eq!(613, Binomial::new(1000, 0.6).unwrap().sample(&mut krull));
+        assert_eq!(18, Geometric::new(0.1).unwrap().sample(&mut krull));
+        assert_eq!(
+            14,
+            NegativeBinomial::new(4.5, 0.25).unwrap().sample(&mut krull)
+        );
+        assert_eq!(
+            9,
+            Hypergeometric::new(100, 30, 20).unwrap().sample(&mut krull)
+        );
+        assert_eq!(12, krull.position());
+
+        // Certain outcomes consume no outputs.
+        assert_eq!(0, Binomial::new(0, 0.5).unwrap().sample(&mut krull));
+        assert_eq!(7, Binomial::new(7, 1.0).unwrap().sample(&mut krull));
+        assert_eq!(0, Binomial::new(7, 0.0).unwrap().sample(&mut krull));
+        assert_eq!(0, Geometric::new(1.0).unwrap().sample(&mut krull));
+        assert_eq!(
+            0,
+            NegativeBinomial::new(2.0, 1.0).unwrap().sample(&mut krull)
+        );
+        assert_eq!(
+            5,
+            Hypergeometric::new(10, 10, 5).unwrap().sample(&mut krull)
+        );
+        assert_eq!(
+            3,
+            Hypergeometric::new(10, 3, 10).unwrap().sample(&mut krull)
+        );
+        assert_eq!(12, krull.position());
+
+        assert!(Poisson::new(0.0).is_none());
+        assert!(Poisson::new(f64::NAN).is_none());
+        assert!(Poisson::new(1.0e19).is_none());
+        assert!(Binomial::new(10, 1.5).is_none());
+        assert!(Binomial::new(10, f64::NAN).is_none());
+        assert!(Geometric::new(0.0).is_none());
+        assert!(NegativeBinomial::new(0.0, 0.5).is_none());
+        assert!(NegativeBinomial::new(1.0, 0.0).is_none());
+        assert!(Hypergeometric::new(10, 11, 5).is_none());
+        assert!(Hypergeometric::new(10, 5, 11).is_none());
+
+        // Probabilities sum to 1.
+        let sum: f64 = (0..=1000)
+            .map(|k| exp(ln_binomial_pmf(k, 1000, 0.3, 0.7)))
+            .sum();
+        assert!((sum - 1.0).abs() < 1.0e-12);
+        let sum: f64 = (0..200).map(|k| exp(ln_poisson_pmf(k, 50.0))).sum();
+        assert!((sum - 1.0).abs() < 1.0e-12);
+
+        let n = 1 << 14;
+        let mut krull = Krull65::from_64(1);
+
+        // Both sampling methods are exercised on each side of p = 1/2.
+        for lambda in [0.5, 9.5, 10.0, 250.0, 1.0e9] {
+            let poisson = Poisson::new(lambda).unwrap();
+            check_moments(&mut krull, n, &poisson, lambda, lambda);
+        }
+        for (trials, p) in [(40, 0.2), (40, 0.8), (500, 0.1), (500, 0.9), (1 << 40, 0.5)] {
+            let binomial = Binomial::new(trials, p).unwrap();
+            let mean = trials as f64 * p;
+            check_moments(&mut krull, n, &binomial, mean, mean * (1.0 - p));
+        }
+        for p in [0.9, 0.1, 1.0e-6] {
+            let geometric = Geometric::new(p).unwrap();
+            let q = 1.0 - p;
+            check_moments(&mut krull, n, &geometric, q / p, q / (p * p));
+        }
+        for (r, p) in [(0.5, 0.5), (3.0, 0.2), (100.0, 0.01)] {
+            let negative_binomial = NegativeBinomial::new(r, p).unwrap();
+            let q = 1.0 - p;
+            check_moments(
+                &mut krull,
+                n,
+                &negative_binomial,
+                r * q / p,
+                r * q / (p * p),
+            );
+        }
+        // Inversion is used for the first two, and rejection for the rest.
+        for (total, successes, draws) in [
+            (100, 30, 20),
+            (50, 45, 30),
+            (1 << 20, 1 << 10, 1 << 19),
+            (100000, 99000, 60000),
+            (1 << 62, 1 << 61, 1 << 61),
+        ] {
+            let hypergeometric = Hypergeometric::new(total, successes, draws).unwrap();
+            let (total, successes, draws) = (total as f64, successes as f64, draws as f64);
+            let mean = draws * successes / total;
+            let var = mean * (total - successes) * (total - draws) / (total * (total - 1.0));
+            check_moments(&mut krull, n, &hypergeometric, mean, var);
+        }
+    }
+}
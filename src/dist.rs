@@ -0,0 +1,438 @@
+use super::math::{exp, ln, sqrt};
+use super::ziggurat::*;
+use super::{Float, RngCore};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Continuous distributions for any RNG. The normal and exponential distributions
+// are sampled with the Ziggurat method, and the rest are built on them.
+// Elementary functions come from our own math module, so results are identical
+// on all platforms. The algorithms, and thus the values sampled and the number of
+// outputs consumed, are frozen within a crate version.
+
+/// Probability distribution of values of type T.
+pub trait Distribution<T> {
+    /// Draws a sample from the distribution.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> T;
+}
+
+/// Samples the standard normal distribution with mean 0 and standard deviation 1.
+/// Uses the Ziggurat method, which consumes one output 98.8% of the time.
+pub fn standard_normal<R: RngCore + ?Sized>(rng: &mut R) -> f64 {
+    loop {
+        let bits = rng.next_u64();
+        // Layer index comes from the low bits and a uniform number in [-1, 1) from the high bits.
+        let i = (bits & 0xff) as usize;
+        let u = (bits >> 11) as f64 * (1.0 / (1u64 << 52) as f64) - 1.0;
+        let x = u * ZIG_NORM_X[i];
+        if x.abs() < ZIG_NORM_X[i + 1] {
+            return x;
+        }
+        if i == 0 {
+            // Sample the tail with Marsaglia's method.
+            loop {
+                let x = -ln(rng.next_f64_open()) * (1.0 / ZIG_NORM_R);
+                let y = -ln(rng.next_f64_open());
+                if y + y >= x * x {
+                    return if u < 0.0 {
+                        -ZIG_NORM_R - x
+                    } else {
+                        ZIG_NORM_R + x
+                    };
+                }
+            }
+        }
+        if ZIG_NORM_F[i + 1] + (ZIG_NORM_F[i] - ZIG_NORM_F[i + 1]) * rng.next_f64()
+            < exp(-0.5 * x * x)
+        {
+            return x;
+        }
+    }
+}
+
+/// Samples the standard exponential distribution with rate 1.
+/// Uses the Ziggurat method, which consumes one output 98.9% of the time.
+pub fn standard_exp<R: RngCore + ?Sized>(rng: &mut R) -> f64 {
+    loop {
+        let bits = rng.next_u64();
+        // Layer index comes from the low bits and a uniform number in [0, 1) from the high bits.
+        let i = (bits & 0xff) as usize;
+        let u = (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64);
+        let x = u * ZIG_EXP_X[i];
+        if x < ZIG_EXP_X[i + 1] {
+            return x;
+        }
+        if i == 0 {
+            // The tail is a shifted exponential distribution.
+            return ZIG_EXP_R - ln(rng.next_f64_open());
+        }
+        if ZIG_EXP_F[i + 1] + (ZIG_EXP_F[i] - ZIG_EXP_F[i + 1]) * rng.next_f64() < exp(-x) {
+            return x;
+        }
+    }
+}
+
+/// Normal distribution.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Normal {
+    mean: f64,
+    std_dev: f64,
+}
+
+impl Normal {
+    /// Creates a normal distribution. Returns `None` unless the mean is finite
+    /// and the standard deviation is finite and non-negative.
+    pub fn new(mean: f64, std_dev: f64) -> Option<Self> {
+        if mean.is_finite() && std_dev.is_finite() && std_dev >= 0.0 {
+            Some(Normal { mean, std_dev })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the mean.
+    #[inline]
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Returns the standard deviation.
+    #[inline]
+    pub fn std_dev(&self) -> f64 {
+        self.std_dev
+    }
+}
+
+impl Distribution<f64> for Normal {
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> f64 {
+        self.mean + self.std_dev * standard_normal(rng)
+    }
+}
+
+/// Log-normal distribution: the exponential of a normal distribution.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LogNormal {
+    normal: Normal,
+}
+
+impl LogNormal {
+    /// Creates a log-normal distribution from the mean `mu` and standard deviation `sigma`
+    /// of the underlying normal distribution. Returns `None` unless `mu` is finite
+    /// and `sigma` is finite and non-negative.
+    pub fn new(mu: f64, sigma: f64) -> Option<Self> {
+        Normal::new(mu, sigma).map(|normal| LogNormal { normal })
+    }
+}
+
+impl Distribution<f64> for LogNormal {
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> f64 {
+        exp(self.normal.sample(rng))
+    }
+}
+
+/// Exponential distribution.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Exp {
+    rate: f64,
+}
+
+impl Exp {
+    /// Creates an exponential distribution with mean 1 / rate.
+    /// Returns `None` unless the rate is finite and positive.
+    pub fn new(rate: f64) -> Option<Self> {
+        if rate.is_finite() && rate > 0.0 {
+            Some(Exp { rate })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rate.
+    #[inline]
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+}
+
+impl Distribution<f64> for Exp {
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> f64 {
+        standard_exp(rng) / self.rate
+    }
+}
+
+/// Gamma distribution.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Gamma {
+    shape: f64,
+    scale: f64,
+    /// Parameters of the Marsaglia-Tsang method, which samples shape + 1 if shape < 1.
+    d: f64,
+    c: f64,
+}
+
+impl Gamma {
+    /// Creates a gamma distribution with mean shape * scale.
+    /// Returns `None` unless shape and scale are finite and positive.
+    pub fn new(shape: f64, scale: f64) -> Option<Self> {
+        if shape.is_finite() && shape > 0.0 && scale.is_finite() && scale > 0.0 {
+            let d = if shape < 1.0 { shape + 1.0 } else { shape } - 1.0 / 3.0;
+            Some(Gamma {
+                shape,
+                scale,
+                d,
+                c: 1.0 / sqrt(9.0 * d),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the shape.
+    #[inline]
+    pub fn shape(&self) -> f64 {
+        self.shape
+    }
+
+    /// Returns the scale.
+    #[inline]
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+}
+
+impl Distribution<f64> for Gamma {
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> f64 {
+        // Marsaglia, G. and Tsang, W. W., A Simple Method for Generating Gamma Variables (2000).
+        let g = loop {
+            let x = standard_normal(rng);
+            let v = 1.0 + self.c * x;
+            if v <= 0.0 {
+                continue;
+            }
+            let v = v * v * v;
+            let u = rng.next_f64_open();
+            let xx = x * x;
+            if u < 1.0 - 0.0331 * xx * xx || ln(u) < 0.5 * xx + self.d * (1.0 - v + ln(v)) {
+                break self.d * v;
+            }
+        };
+        if self.shape < 1.0 {
+            // Boost with Gamma(a) = Gamma(a + 1) * U**(1 / a).
+            g * exp(ln(rng.next_f64_open()) / self.shape) * self.scale
+        } else {
+            g * self.scale
+        }
+    }
+}
+
+/// Beta distribution.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Beta {
+    alpha: Gamma,
+    beta: Gamma,
+}
+
+impl Beta {
+    /// Creates a beta distribution with mean alpha / (alpha + beta).
+    /// Returns `None` unless alpha and beta are finite and positive.
+    pub fn new(alpha: f64, beta: f64) -> Option<Self> {
+        Some(Beta {
+            alpha: Gamma::new(alpha, 1.0)?,
+            beta: Gamma::new(beta, 1.0)?,
+        })
+    }
+}
+
+impl Distribution<f64> for Beta {
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> f64 {
+        // X / (X + Y) with X ~ Gamma(alpha), Y ~ Gamma(beta).
+        // With tiny parameters, both can underflow to zero.
+        loop {
+            let x = self.alpha.sample(rng);
+            let y = self.beta.sample(rng);
+            if x + y > 0.0 {
+                return x / (x + y);
+            }
+        }
+    }
+}
+
+/// Cauchy distribution.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cauchy {
+    median: f64,
+    scale: f64,
+}
+
+impl Cauchy {
+    /// Creates a Cauchy distribution. Returns `None` unless the median is finite
+    /// and the scale is finite and positive.
+    pub fn new(median: f64, scale: f64) -> Option<Self> {
+        if median.is_finite() && scale.is_finite() && scale > 0.0 {
+            Some(Cauchy { median, scale })
+        } else {
+            None
+        }
+    }
+}
+
+impl Distribution<f64> for Cauchy {
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> f64 {
+        // The ratio of two independent standard normals is standard Cauchy.
+        // A zero denominator is drawn again.
+        let x = standard_normal(rng);
+        let mut y = standard_normal(rng);
+        while y == 0.0 {
+            y = standard_normal(rng);
+        }
+        self.median + self.scale * (x / y)
+    }
+}
+
+/// Student's t-distribution.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StudentT {
+    dof: f64,
+    /// Chi-squared distribution with `dof` degrees of freedom.
+    chi_squared: Gamma,
+}
+
+impl StudentT {
+    /// Creates a t-distribution with `dof` degrees of freedom.
+    /// Returns `None` unless `dof` is finite and positive.
+    pub fn new(dof: f64) -> Option<Self> {
+        Some(StudentT {
+            dof,
+            chi_squared: Gamma::new(0.5 * dof, 2.0)?,
+        })
+    }
+}
+
+impl Distribution<f64> for StudentT {
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> f64 {
+        let x = standard_normal(rng);
+        x / sqrt(self.chi_squared.sample(rng) / self.dof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::float::tests::Replay;
+    use super::super::*;
+    use super::*;
+
+    /// Returns the mean and variance of n samples.
+    fn moments<R: RngCore>(rng: &mut R, n: usize, dist: &impl Distribution<f64>) -> (f64, f64) {
+        let mut sum = 0.0;
+        let mut sum2 = 0.0;
+        for _ in 0..n {
+            let x = dist.sample(rng);
+            sum += x;
+            sum2 += x * x;
+        }
+        let mean = sum / n as f64;
+        (mean, sum2 / n as f64 - mean * mean)
+    }
+
+    /// Returns the fraction of n samples that are less than x.
+    fn fraction_below<R: RngCore>(
+        rng: &mut R,
+        n: usize,
+        dist: &impl Distribution<f64>,
+        x: f64,
+    ) -> f64 {
+        (0..n).filter(|_| dist.sample(rng) < x).count() as f64 / n as f64
+    }
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers. These must not change within a crate version.
+        let mut krull = Krull64::from_64(0);
+        assert_eq!(-0.29981338062400814, standard_normal(&mut krull));
+        assert_eq!(-2.01314861950117, standard_normal(&mut krull));
+        assert_eq!(0.4999655418395619, standard_exp(&mut krull));
+        assert_eq!(2.5726503327160226, standard_exp(&mut krull));
+        assert_eq!(
+            5.087010441753484,
+            Gamma::new(2.5, 1.0).unwrap().sample(&mut krull)
+        );
+        assert_eq!(
+            0.0006985501542687409,
+            Gamma::new(0.5, 1.0).unwrap().sample(&mut krull)
+        );
+        assert_eq!(9, krull.position());
+
+        assert!(Normal::new(0.0, -1.0).is_none());
+        assert!(Normal::new(f64::NAN, 1.0).is_none());
+        assert!(Exp::new(0.0).is_none());
+        assert!(Gamma::new(1.0, f64::INFINITY).is_none());
+        assert!(Beta::new(0.0, 1.0).is_none());
+        assert!(Cauchy::new(0.0, 0.0).is_none());
+        assert!(StudentT::new(-1.0).is_none());
+        assert!(LogNormal::new(0.0, f64::NAN).is_none());
+
+        let n = 1 << 15;
+        let mut krull = Krull65::from_64(1);
+
+        let (mean, var) = moments(&mut krull, n, &Normal::new(1.0, 2.0).unwrap());
+        assert!((mean - 1.0).abs() < 0.05 && (var - 4.0).abs() < 0.1);
+        // Normal tail probabilities are right, including the tail beyond the Ziggurat.
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let p = fraction_below(&mut krull, n, &normal, -2.0);
+        assert!((p - 0.02275).abs() < 0.003);
+        let mut tail = 0;
+        for _ in 0..1 << 20 {
+            tail += (standard_normal(&mut krull).abs() > ZIG_NORM_R) as u32;
+        }
+        // The expected count is 2**20 * 2.58e-4 = 270.6.
+        assert!(tail.abs_diff(271) < 60);
+
+        let (mean, var) = moments(&mut krull, n, &Exp::new(2.0).unwrap());
+        assert!((mean - 0.5).abs() < 0.01 && (var - 0.25).abs() < 0.02);
+        let p = fraction_below(&mut krull, n, &Exp::new(1.0).unwrap(), 3.0);
+        assert!((p - 0.95021).abs() < 0.005);
+
+        for (shape, scale) in [(0.2, 1.0), (1.0, 2.0), (3.5, 0.5), (100.0, 1.0)] {
+            let (mean, var) = moments(&mut krull, n, &Gamma::new(shape, scale).unwrap());
+            let (mean0, var0) = (shape * scale, shape * scale * scale);
+            assert!((mean / mean0 - 1.0).abs() < 0.03);
+            assert!((var / var0 - 1.0).abs() < 0.06);
+        }
+
+        for (alpha, beta) in [(0.5, 0.5), (2.0, 5.0), (1.0, 1.0)] {
+            let (mean, var) = moments(&mut krull, n, &Beta::new(alpha, beta).unwrap());
+            let mean0 = alpha / (alpha + beta);
+            let var0 = alpha * beta / ((alpha + beta) * (alpha + beta) * (alpha + beta + 1.0));
+            assert!((mean - mean0).abs() < 0.01);
+            assert!((var / var0 - 1.0).abs() < 0.05);
+        }
+
+        // Quartiles of the Cauchy distribution are median -/+ scale.
+        let cauchy = Cauchy::new(1.0, 2.0).unwrap();
+        assert!((fraction_below(&mut krull, n, &cauchy, -1.0) - 0.25).abs() < 0.01);
+        assert!((fraction_below(&mut krull, n, &cauchy, 1.0) - 0.5).abs() < 0.01);
+        assert!((fraction_below(&mut krull, n, &cauchy, 3.0) - 0.75).abs() < 0.01);
+        // Normals 0.5 * R, 0 and -0.5 * R from the first Ziggurat layer.
+        let outputs = [(3 << 62) | 1, (1 << 63) | 1, (1 << 62) | 1];
+        assert_eq!(-1.0, cauchy.sample(&mut Replay(&outputs)));
+
+        let log_normal = LogNormal::new(1.0, 0.5).unwrap();
+        let p = fraction_below(&mut krull, n, &log_normal, exp(1.0));
+        assert!((p - 0.5).abs() < 0.01);
+        let (mean, _) = moments(&mut krull, n, &log_normal);
+        assert!((mean - exp(1.125)).abs() < 0.05);
+
+        // The 0.975 quantile of t(5) is 2.5706.
+        let t = StudentT::new(5.0).unwrap();
+        let (mean, var) = moments(&mut krull, n, &t);
+        assert!(mean.abs() < 0.05 && (var - 5.0 / 3.0).abs() < 0.15);
+        assert!((fraction_below(&mut krull, n, &t, 2.5706) - 0.975).abs() < 0.005);
+    }
+}
@@ -0,0 +1,289 @@
+use super::{Krull64, Krull65, RngCore};
+
+// Floating point numbers are made from the high bits of 64-bit outputs.
+// The standard variants return multiples of a fixed spacing, 2**-53 (f64) or 2**-24 (f32).
+// The dense variants, after Downey, A. B., Generating Pseudo-random Floating-Point Values (2007),
+// choose the binade [2**e, 2**(e + 1)) with probability 2**e from the leading zeros
+// of further random bits, and then a uniform mantissa inside the binade.
+// Every float x in [0, 1) is returned with probability equal to the distance to
+// the next float, as if a uniform real number was rounded down.
+//
+// The algorithms and the number of outputs they consume are part of the stable API.
+
+/// Converts a 64-bit random number to a float in [0, 1) using the 53 high bits.
+#[inline]
+const fn to_f64(x: u64) -> f64 {
+    (x >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Converts a 64-bit random number to a float in [0, 1) using the 24 high bits.
+#[inline]
+const fn to_f32(x: u64) -> f32 {
+    (x >> 40) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+/// Uniform floating point numbers for any RNG. Each method consumes one `next_u64()` output,
+/// except for the dense variants, which rarely consume more. Results are stable across
+/// crate versions and platforms. Krull64 and Krull65 provide these methods inherently.
+pub trait Float: RngCore {
+    /// Returns a uniformly distributed number in [0, 1)
+    /// that is a multiple of 2**-53.
+    fn next_f64(&mut self) -> f64 {
+        to_f64(self.next_u64())
+    }
+
+    /// Returns a uniformly distributed number in the open interval (0, 1)
+    /// that is an odd multiple of 2**-53.
+    fn next_f64_open(&mut self) -> f64 {
+        ((self.next_u64() >> 12) as f64 + 0.5) * (1.0 / (1u64 << 52) as f64)
+    }
+
+    /// Returns a uniformly distributed number in the closed interval [0, 1]
+    /// that is a multiple of 1 / (2**53 - 1).
+    fn next_f64_closed(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / ((1u64 << 53) - 1) as f64
+    }
+
+    /// Returns a uniformly distributed number in [0, 1) that can be any float in the interval.
+    /// Each float is returned with probability equal to the distance to the next float.
+    /// Consumes more than one output with probability 2**-12.
+    fn next_f64_dense(&mut self) -> f64 {
+        let x = self.next_u64();
+        let mantissa = x >> 12;
+        // The 12 low bits start the search for the exponent.
+        let mut exponent = -1 - ((x << 52).leading_zeros() as i32).min(12);
+        if exponent == -13 {
+            loop {
+                let y = self.next_u64();
+                exponent -= y.leading_zeros() as i32;
+                if y != 0 || exponent < -1022 {
+                    break;
+                }
+            }
+        }
+        if exponent < -1022 {
+            // Subnormal numbers are evenly spaced below 2**-1022.
+            f64::from_bits(mantissa)
+        } else {
+            f64::from_bits((((exponent + 1023) as u64) << 52) | mantissa)
+        }
+    }
+
+    /// Returns a uniformly distributed number in [0, 1)
+    /// that is a multiple of 2**-24.
+    fn next_f32(&mut self) -> f32 {
+        to_f32(self.next_u64())
+    }
+
+    /// Returns a uniformly distributed number in the open interval (0, 1)
+    /// that is an odd multiple of 2**-24.
+    fn next_f32_open(&mut self) -> f32 {
+        ((self.next_u64() >> 41) as f32 + 0.5) * (1.0 / (1u32 << 23) as f32)
+    }
+
+    /// Returns a uniformly distributed number in the closed interval [0, 1]
+    /// that is a multiple of 1 / (2**24 - 1).
+    fn next_f32_closed(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / ((1u32 << 24) - 1) as f32
+    }
+
+    /// Returns a uniformly distributed number in [0, 1) that can be any float in the interval.
+    /// Each float is returned with probability equal to the distance to the next float.
+    /// Consumes more than one output with probability 2**-41.
+    fn next_f32_dense(&mut self) -> f32 {
+        let x = self.next_u64();
+        let mantissa = (x >> 41) as u32;
+        // The 41 low bits start the search for the exponent.
+        let mut exponent = -1 - ((x << 23).leading_zeros() as i32).min(41);
+        if exponent == -42 {
+            loop {
+                let y = self.next_u64();
+                exponent -= y.leading_zeros() as i32;
+                if y != 0 || exponent < -126 {
+                    break;
+                }
+            }
+        }
+        if exponent < -126 {
+            // Subnormal numbers are evenly spaced below 2**-126.
+            f32::from_bits(mantissa)
+        } else {
+            f32::from_bits((((exponent + 127) as u32) << 23) | mantissa)
+        }
+    }
+}
+
+impl<R: RngCore + ?Sized> Float for R {}
+
+/// Implements the methods of `Float` inherently so they can be used without an import,
+/// and adds random access to floats.
+macro_rules! impl_float {
+    ( $( ($t:ty, $stream:ty) ),* ) => {
+    $( impl $t {
+        /// Returns a uniformly distributed number in [0, 1) that is a multiple of 2**-53.
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f64(&mut self) -> f64 {
+            Float::next_f64(self)
+        }
+
+        /// Returns a uniformly distributed number in the open interval (0, 1).
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f64_open(&mut self) -> f64 {
+            Float::next_f64_open(self)
+        }
+
+        /// Returns a uniformly distributed number in the closed interval [0, 1].
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f64_closed(&mut self) -> f64 {
+            Float::next_f64_closed(self)
+        }
+
+        /// Returns a uniformly distributed number in [0, 1) that can be any float in the interval.
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f64_dense(&mut self) -> f64 {
+            Float::next_f64_dense(self)
+        }
+
+        /// Returns a uniformly distributed number in [0, 1) that is a multiple of 2**-24.
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f32(&mut self) -> f32 {
+            Float::next_f32(self)
+        }
+
+        /// Returns a uniformly distributed number in the open interval (0, 1).
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f32_open(&mut self) -> f32 {
+            Float::next_f32_open(self)
+        }
+
+        /// Returns a uniformly distributed number in the closed interval [0, 1].
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f32_closed(&mut self) -> f32 {
+            Float::next_f32_closed(self)
+        }
+
+        /// Returns a uniformly distributed number in [0, 1) that can be any float in the interval.
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn next_f32_dense(&mut self) -> f32 {
+            Float::next_f32_dense(self)
+        }
+
+        /// Returns the float in [0, 1) at the given position of the given stream
+        /// without constructing a generator. The nth call to `next_f64()`
+        /// from position 0 returns `f64_at(stream, n)`.
+        pub const fn f64_at(stream: $stream, position: u128) -> f64 {
+            to_f64(<$t>::output_at(stream, position))
+        }
+
+        /// Returns the float in [0, 1) at the given position of the given stream
+        /// without constructing a generator. The nth call to `next_f32()`
+        /// from position 0 returns `f32_at(stream, n)`.
+        pub const fn f32_at(stream: $stream, position: u128) -> f32 {
+            to_f32(<$t>::output_at(stream, position))
+        }
+    }) *
+    }
+}
+impl_float! { (Krull64, u64), (Krull65, u128) }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::*;
+    use super::*;
+
+    /// RNG that replays a fixed sequence of outputs.
+    pub(crate) struct Replay<'a>(pub(crate) &'a [u64]);
+
+    impl RngCore for Replay<'_> {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            let (x, rest) = self.0.split_first().unwrap();
+            self.0 = rest;
+            *x
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn run_tests() {
+        // Extreme values.
+        let max = u64::MAX;
+        assert_eq!(0.0, Replay(&[0]).next_f64());
+        assert_eq!(1.0 - f64::EPSILON / 2.0, Replay(&[max]).next_f64());
+        assert_eq!(1.0 - f32::EPSILON / 2.0, Replay(&[max]).next_f32());
+        assert_eq!(f64::EPSILON / 2.0, Replay(&[0]).next_f64_open());
+        assert_eq!(1.0 - f64::EPSILON / 2.0, Replay(&[max]).next_f64_open());
+        assert_eq!(f32::EPSILON / 2.0, Replay(&[0]).next_f32_open());
+        assert_eq!(1.0 - f32::EPSILON / 2.0, Replay(&[max]).next_f32_open());
+        assert_eq!(0.0, Replay(&[0]).next_f64_closed());
+        assert_eq!(1.0, Replay(&[max]).next_f64_closed());
+        assert_eq!(0.0, Replay(&[0]).next_f32_closed());
+        assert_eq!(1.0, Replay(&[max]).next_f32_closed());
+        assert_eq!(1.0 - f64::EPSILON / 2.0, Replay(&[max]).next_f64_dense());
+        assert_eq!(1.0 - f32::EPSILON / 2.0, Replay(&[max]).next_f32_dense());
+
+        // Dense floats pick the binade from leading zeros.
+        let pow2 = |e: i32| f64::from_bits(((1023 + e) as u64) << 52);
+        let m = 0x5555_5555_5555_5000;
+        let f = 1.0 + to_f64(m);
+        assert_eq!(f * pow2(-1), Replay(&[m | 0x800]).next_f64_dense());
+        assert_eq!(f * pow2(-2), Replay(&[m | 0x400]).next_f64_dense());
+        assert_eq!(f * pow2(-14), Replay(&[m, 1 << 62]).next_f64_dense());
+        let mut zeros = [0; 17];
+        zeros[16] = 1 << 14;
+        assert_eq!(pow2(-1022), Replay(&zeros).next_f64_dense());
+        zeros[0] = m;
+        zeros[16] = 0;
+        assert_eq!(f64::from_bits(m >> 12), Replay(&zeros).next_f64_dense());
+        assert_eq!(pow2(-42) as f32, Replay(&[0, 1 << 63]).next_f32_dense());
+        assert_eq!(f32::from_bits(1), Replay(&[1 << 41, 0, 0]).next_f32_dense());
+
+        // Random access agrees with serial generation.
+        let mut krull64 = Krull64::from_64(1);
+        let mut krull65 = Krull65::from_64(1);
+        for i in 1..=10 {
+            assert_eq!(Krull64::f64_at(1, i), krull64.next_f64());
+            assert_eq!(Krull65::f64_at(1, i), krull65.next_f64());
+        }
+        for i in 11..=20 {
+            assert_eq!(Krull64::f32_at(1, i), krull64.next_f32());
+            assert_eq!(Krull65::f32_at(1, i), krull65.next_f32());
+        }
+        const X: f64 = Krull64::f64_at(1, 1);
+        assert_eq!(Krull64::f64_at(1, 1), X);
+
+        // Dense floats are uniform: their mean is 1/2 and small values are frequent enough.
+        let n = 1 << 14;
+        let mut sum = 0.0;
+        let mut small = 0;
+        for _ in 0..n {
+            let x = krull64.next_f64_dense();
+            let y = krull65.next_f32_dense();
+            assert!((0.0..1.0).contains(&x));
+            assert!((0.0..1.0).contains(&y));
+            sum += x + y as f64;
+            small += (x < 1.0 / 1024.0) as u32 + (y < 1.0 / 1024.0) as u32;
+        }
+        assert!((sum / (2 * n) as f64 - 0.5).abs() < 0.01);
+        assert!(small.abs_diff(2 * n / 1024) < 24);
+    }
+}
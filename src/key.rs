@@ -0,0 +1,151 @@
+use super::krull64::hash;
+
+// The key hash turns byte keys such as "world/terrain/chunk(12,-5)" into stream numbers.
+// Streams derived from keys are part of the reproducibility contract,
+// so the hash is versioned: the output of a version never changes.
+//
+// The seed is mixed first, so differences in the seed cannot cancel differences in the key.
+// The key is then absorbed in 64-bit little-endian words, zero padding the final word,
+// by chaining the Krull64 output hash. The key length is mixed in last
+// so keys that differ only in trailing zero bytes hash differently.
+// The hash is not cryptographic: keys should not come from an adversary.
+
+/// Version of the key hash used by `hash_key_64` and `hash_key_128`.
+/// Streams derived from keys are stable within a version.
+pub const KEY_HASH_VERSION: u32 = 1;
+
+/// Keys XORed into the chained hash lanes.
+const LANE_KEY_0: u64 = 0x9e3779b97f4a7c15;
+const LANE_KEY_1: u64 = 0xc2b2ae3d27d4eb4f;
+
+/// Keys XORed into the seed words before they are mixed.
+const SEED_KEY_0: u64 = 0x165667b19e3779f9;
+const SEED_KEY_1: u64 = 0x27d4eb2f165667c5;
+
+/// Reads the little-endian word at byte offset i of key. Missing bytes are zero.
+const fn read_word(key: &[u8], i: usize) -> u64 {
+    let mut word = 0;
+    let mut j = 0;
+    while j < 8 && i + j < key.len() {
+        word |= (key[i + j] as u64) << (j << 3);
+        j += 1;
+    }
+    word
+}
+
+/// Hashes the byte key into 64 bits. The seed selects an independent hash function.
+pub const fn hash_key_64(seed: u64, key: &[u8]) -> u64 {
+    let mut h = hash(seed ^ SEED_KEY_0);
+    let mut i = 0;
+    loop {
+        h = hash(h ^ read_word(key, i) ^ LANE_KEY_0);
+        i += 8;
+        if i >= key.len() {
+            break;
+        }
+    }
+    hash(h ^ key.len() as u64)
+}
+
+/// Hashes the byte key into 128 bits. The seed selects an independent hash function.
+pub const fn hash_key_128(seed: u128, key: &[u8]) -> u128 {
+    // Both lanes depend on both seed words. As the hash is bijective,
+    // distinct seeds give distinct lane states.
+    let high = hash((seed >> 64) as u64 ^ SEED_KEY_1);
+    let mut h0 = hash(seed as u64 ^ SEED_KEY_0 ^ high);
+    let mut h1 = hash(high ^ h0);
+    let mut i = 0;
+    loop {
+        let word = read_word(key, i);
+        h0 = hash(h0 ^ word ^ LANE_KEY_0);
+        h1 = hash(h1 ^ word ^ LANE_KEY_1).wrapping_add(h0);
+        i += 8;
+        if i >= key.len() {
+            break;
+        }
+    }
+    let h0 = hash(h0 ^ key.len() as u64);
+    let h1 = hash(h1 ^ key.len() as u64).wrapping_add(h0);
+    ((h1 as u128) << 64) | (h0 as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers of version 1. These must never change.
+        assert_eq!(1, KEY_HASH_VERSION);
+        assert_eq!(0x2e9bacfcbd45230d, hash_key_64(0, b""));
+        assert_eq!(0x56a6856383ec2c88, hash_key_64(0, b"world"));
+        assert_eq!(
+            0x8658bed5b47ee684,
+            hash_key_64(0, b"world/terrain/chunk(12,-5)")
+        );
+        assert_eq!(0x8907b14edb8a8b60e659b25ceaf5be5c, hash_key_128(0, b""));
+        assert_eq!(
+            0x7bf8f9a180ae4b2cef5a5dfc277fdfab,
+            hash_key_128(0, b"world/terrain/chunk(12,-5)")
+        );
+
+        let keys: [&[u8]; 9] = [
+            b"",
+            b"\0",
+            b"\0\0\0\0\0\0\0\0",
+            b"\0\0\0\0\0\0\0\0\0",
+            b"a",
+            b"b",
+            b"world",
+            b"world/terrain",
+            b"world/terrain/chunk(12,-5)",
+        ];
+        for (i, key1) in keys.iter().enumerate() {
+            for key2 in keys[i + 1..].iter() {
+                assert_ne!(hash_key_64(0, key1), hash_key_64(0, key2));
+                assert_ne!(hash_key_128(0, key1), hash_key_128(0, key2));
+            }
+            assert_ne!(hash_key_64(0, key1), hash_key_64(1, key1));
+            assert_ne!(hash_key_128(0, key1), hash_key_128(1 << 64, key1));
+        }
+
+        // Differences in the seed do not cancel differences in the key.
+        assert_ne!(hash_key_64(0, b"\x01"), hash_key_64(1, b"\0"));
+        assert_ne!(
+            Krull64::from_64(2).derive(b"a"),
+            Krull64::from_64(1).derive(b"b")
+        );
+        assert_ne!(
+            Krull65::from_128((3 << 64) | 3).derive(b"\0"),
+            Krull65::from_128(0).derive(b"\x03")
+        );
+
+        // Keys are usable in constant expressions.
+        const STREAM: u64 = hash_key_64(0, b"world");
+        assert_eq!(STREAM, Krull64::from_key(b"world").stream());
+
+        // Derivation from the root generator equals construction from the key.
+        for key in keys {
+            assert_eq!(Krull64::from_key(key), Krull64::new().derive(key));
+            assert_eq!(Krull65::from_key(key), Krull65::new().derive(key));
+        }
+
+        // Derived streams depend on the stream of the parent but not its position.
+        let mut world = Krull65::from_key(b"world");
+        let terrain = world.derive(b"terrain");
+        world.step();
+        assert_eq!(terrain, world.derive(b"terrain"));
+        assert_eq!(0, terrain.position());
+        assert_ne!(terrain, Krull65::from_key(b"world/terrain"));
+        assert_eq!(
+            hash_key_128(Krull65::from_key(b"world").stream(), b"terrain"),
+            terrain.stream()
+        );
+        let mut world = Krull64::from_key(b"world");
+        let terrain = world.derive(b"terrain");
+        world.step();
+        assert_eq!(terrain, world.derive(b"terrain"));
+        assert_ne!(terrain, Krull64::from_key(b"world/terrain"));
+    }
+}
@@ -1,3 +1,8 @@
+use super::hash_key_64;
+use super::lattice_position;
+use super::lcg::Affine;
+use super::{Block, Budgeted, Leap, Leapfrog, Outputs, Overrun, Partition};
+use core::ops::Range;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use wrapping_arithmetic::wrappit;
@@ -16,11 +21,11 @@ use wrapping_arithmetic::wrappit;
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Krull64 {
     /// LCG state low bits.
-    lcg0: u64,
+    pub(crate) lcg0: u64,
     /// LCG state high bits.
-    lcg1: u64,
+    pub(crate) lcg1: u64,
     /// Stream number.
-    stream: u64,
+    pub(crate) stream: u64,
 }
 
 // Stream position is measured in relation to an origin LCG state at position 0.
@@ -28,15 +33,65 @@ pub struct Krull64 {
 // in order to desynchronize the streams. Here we invert all the bits,
 // which potentially enhances compression of RNGs at position 0 when serialized.
 #[inline]
-fn origin_0(stream: u64) -> u64 {
+const fn origin_0(stream: u64) -> u64 {
     !stream
 }
 
 #[inline]
-fn origin_128(stream: u64) -> u128 {
+const fn origin_128(stream: u64) -> u128 {
     origin_0(stream) as u128
 }
 
+#[inline]
+const fn increment_128(stream: u64) -> u128 {
+    // LCG increment is odd in full period sequences.
+    // Unlike with LCG multipliers, any odd increment works fine.
+    // Flip of increment bit B causes changes with a period of 2**(128 - B):
+    // LCG sequences that differ only in high bits of the increment are correlated.
+    // So it's important to rely on the low increment bits only.
+    // The increment is a mirror image of the state in this sense,
+    // as in state it is the low bits that repeat.
+    ((stream as u128) << 1) | 1
+}
+
+/// Advances the LCG by one step. Returns the new (lcg0, lcg1).
+#[wrappit]
+#[inline]
+pub(crate) fn step_lcg(lcg0: u64, lcg1: u64, stream: u64) -> (u64, u64) {
+    let multiplier = super::LCG_M65_1 as u64;
+    // We can get a widening 64-to-128-bit multiply by casting the arguments from 64 bits.
+    // We also add the increment in 128-bit to get the carry for free.
+    let lcg = (lcg0 as u128) * multiplier as u128 + increment_128(stream);
+    (lcg as u64, ((lcg >> 64) as u64) + lcg1 * multiplier + lcg0)
+}
+
+/// Inverse of the LCG multiplier modulo 2**128.
+const MULTIPLIER_INVERSE: u128 = crate::lcg::get_inverse_128(super::LCG_M65_1);
+
+/// Number of LCG copies that `fill_u64` and `fill_u32` advance side by side.
+/// The loop in `fill` is unrolled for this number.
+const FILL_LANES: usize = 4;
+
+/// Buffers with fewer elements than this per lane are filled serially.
+const FILL_MIN_BLOCK: usize = 16;
+
+/// Key XORed into outputs before hashing them into forked stream numbers.
+pub(crate) const FORK_KEY: u64 = 0x9e3779b97f4a7c15;
+
+/// Output hash of Krull64. Bijective.
+#[wrappit]
+#[inline]
+pub(crate) const fn hash(x: u64) -> u64 {
+    // We want the output stage to pass tests also as an indexed RNG.
+    // It was tested with PractRand to 1 TB in this use.
+    // The output hash is a combination of stages from SplitMix64
+    // combined with a final stage from a hash by degski.
+    let x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9;
+    let x = (x ^ (x >> 27)) * 0x94d049bb133111eb;
+    let x = (x ^ (x >> 31)) * 0xd6e8feb86659fd93;
+    x ^ (x >> 32)
+}
+
 impl Krull64 {
     #[inline]
     fn lcg_128(&self) -> u128 {
@@ -44,8 +99,9 @@ impl Krull64 {
     }
 
     #[inline]
-    fn multiplier(&self) -> u64 {
-        super::LCG_M65_1 as u64
+    fn set_lcg_128(&mut self, lcg: u128) {
+        self.lcg0 = lcg as u64;
+        self.lcg1 = (lcg >> 64) as u64;
     }
 
     #[inline]
@@ -55,14 +111,7 @@ impl Krull64 {
 
     #[inline]
     fn increment_128(&self) -> u128 {
-        // LCG increment is odd in full period sequences.
-        // Unlike with LCG multipliers, any odd increment works fine.
-        // Flip of increment bit B causes changes with a period of 2**(128 - B):
-        // LCG sequences that differ only in high bits of the increment are correlated.
-        // So it's important to rely on the low increment bits only.
-        // The increment is a mirror image of the state in this sense,
-        // as in state it is the low bits that repeat.
-        ((self.stream as u128) << 1) | 1
+        increment_128(self.stream)
     }
 
     /// Origin is LCG state at position 0 in current stream.
@@ -78,40 +127,130 @@ impl Krull64 {
     }
 
     /// Generates the next 64-bit random number.
-    #[wrappit]
     #[inline]
     pub fn step(&mut self) -> u64 {
-        // We can get a widening 64-to-128-bit multiply by casting the arguments from 64 bits.
-        // We also add the increment in 128-bit to get the carry for free.
-        let lcg = (self.lcg0 as u128) * self.multiplier() as u128 + self.increment_128();
-        self.lcg1 = ((lcg >> 64) as u64) + self.lcg1 * self.multiplier() + self.lcg0;
-        self.lcg0 = lcg as u64;
+        (self.lcg0, self.lcg1) = step_lcg(self.lcg0, self.lcg1, self.stream);
+        self.get()
+    }
+
+    /// Steps back to the previous position and returns the 64-bit output there.
+    /// This is the inverse of `step()` and takes about as long.
+    #[wrappit]
+    #[inline]
+    pub fn step_back(&mut self) -> u64 {
+        // The inverse LCG iteration is state <- (state - p) * m^-1.
+        self.set_lcg_128((self.lcg_128() - self.increment_128()) * MULTIPLIER_INVERSE);
         self.get()
     }
 
+    /// Returns the current 64-bit output and steps back to the previous position.
+    /// Repeated calls return the numbers generated by `step()` in reverse order.
+    #[inline]
+    pub fn prev_u64(&mut self) -> u64 {
+        let x = self.get();
+        self.step_back();
+        x
+    }
+
     /// Generates the next 128-bit random number.
     #[inline]
     pub fn step_128(&mut self) -> u128 {
         self.step() as u128 | ((self.step() as u128) << 64)
     }
 
+    /// Fills `dest` with 64-bit random numbers.
+    /// The result is identical to calling `step()` for each element in turn.
+    pub fn fill_u64(&mut self, dest: &mut [u64]) {
+        self.fill(dest, |x| x);
+    }
+
+    /// Fills `dest` with 32-bit random numbers.
+    /// The result is identical to calling `next_u32()` for each element in turn.
+    pub fn fill_u32(&mut self, dest: &mut [u32]) {
+        self.fill(dest, |x| x as u32);
+    }
+
+    /// Fills `dest` with converted 64-bit random numbers in serial order.
+    #[inline]
+    fn fill<T>(&mut self, dest: &mut [T], convert: impl Fn(u64) -> T) {
+        // A serial loop is bound by the latency of the LCG step and the output hash.
+        // We split the buffer into FILL_LANES blocks and generate each with its own copy
+        // of the LCG, so the copies are independent and their hashes pipeline.
+        // Only the starting states need a jump: the copies advance with the 65-bit multiplier.
+        let block = dest.len() / FILL_LANES;
+        let lanes_len = if block >= FILL_MIN_BLOCK {
+            block * FILL_LANES
+        } else {
+            0
+        };
+        let (blocks, rest) = dest.split_at_mut(lanes_len);
+        if lanes_len > 0 {
+            let jump =
+                Affine::from(super::LCG_M65_1_POWERS.get_jump(self.increment_128(), block as u128));
+            let mut lanes = [(0, 0); FILL_LANES];
+            let mut lcg = self.lcg_128();
+            for lane in lanes.iter_mut() {
+                *lane = (lcg as u64, (lcg >> 64) as u64);
+                lcg = jump.apply(lcg);
+            }
+            let (b0, b123) = blocks.split_at_mut(block);
+            let (b1, b23) = b123.split_at_mut(block);
+            let (b2, b3) = b23.split_at_mut(block);
+            for (((x0, x1), x2), x3) in b0.iter_mut().zip(b1).zip(b2).zip(b3) {
+                for lane in lanes.iter_mut() {
+                    *lane = step_lcg(lane.0, lane.1, self.stream);
+                }
+                *x0 = convert(hash(lanes[0].1));
+                *x1 = convert(hash(lanes[1].1));
+                *x2 = convert(hash(lanes[2].1));
+                *x3 = convert(hash(lanes[3].1));
+            }
+            self.set_lcg_128(lcg);
+        }
+        for x in rest.iter_mut() {
+            *x = convert(self.step());
+        }
+    }
+
     /// Returns the current 64-bit output.
-    #[wrappit]
     #[inline]
     pub fn get(&self) -> u64 {
         // Take high 64 bits from the LCG, they are the most random.
         // The 1-to-1 mapping guarantees equidistribution
         // as the rest of the pipeline is bijective.
-        let x = self.lcg1;
+        hash(self.lcg1)
+    }
+
+    /// Returns the 64-bit output at the given position of the given stream
+    /// without constructing a generator. This is a pure function:
+    /// the result is always identical to that of `get()`
+    /// after `set_stream(stream)` and `set_position(position)`.
+    /// In particular, the nth call to `step()` from position 0
+    /// returns `output_at(stream, n)`.
+    pub const fn output_at(stream: u64, position: u128) -> u64 {
+        let lcg =
+            super::LCG_M65_1_POWERS.get_state(increment_128(stream), origin_128(stream), position);
+        hash((lcg >> 64) as u64)
+    }
+
+    /// Returns the 64-bit value of the lattice point (x, y) in the given stream.
+    /// Equal to `at_nd(stream, &[x, y])`. Each point has its own stream position.
+    pub const fn at_2d(stream: u64, x: i64, y: i64) -> u64 {
+        Self::at_nd(stream, &[x, y])
+    }
 
-        // We want the output stage to pass tests also as an indexed RNG.
-        // It was tested with PractRand to 1 TB in this use.
-        // The output hash is a combination of stages from SplitMix64
-        // combined with a final stage from a hash by degski.
-        let x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9;
-        let x = (x ^ (x >> 27)) * 0x94d049bb133111eb;
-        let x = (x ^ (x >> 31)) * 0xd6e8feb86659fd93;
-        x ^ (x >> 32)
+    /// Returns the 64-bit value of the lattice point (x, y, z) in the given stream.
+    /// Equal to `at_nd(stream, &[x, y, z])`.
+    pub const fn at_3d(stream: u64, x: i64, y: i64, z: i64) -> u64 {
+        Self::at_nd(stream, &[x, y, z])
+    }
+
+    /// Returns the 64-bit value of a lattice point of any dimension in the given stream.
+    /// Coordinates are scrambled into a stream position with `lattice_position`,
+    /// so values show no correlations along axes or diagonals.
+    /// Lattices of different dimensions are independent.
+    pub const fn at_nd(stream: u64, coords: &[i64]) -> u64 {
+        Self::output_at(stream, lattice_position(coords))
     }
 
     /// 128-bit version of step() for benchmarking.
@@ -161,27 +300,23 @@ impl Krull64 {
     /// Sets high bits of position from low bits of seed.
     pub fn from_128(seed: u128) -> Self {
         let mut krull = Krull64::from_64(((seed >> 64) ^ seed) as u64);
-        krull.set_position((seed as u128) << 64);
+        krull.set_position(seed << 64);
         krull
     }
 
     /// Jumps forward (if steps > 0) or backward (if steps < 0) or does nothing (if steps = 0).
     /// The stream wraps around, so signed steps can be interpreted as unsigned.
     pub fn jump(&mut self, steps: i128) {
-        let lcg = crate::lcg::get_state(
-            self.multiplier_128(),
+        self.set_lcg_128(super::LCG_M65_1_POWERS.get_state(
             self.increment_128(),
             self.lcg_128(),
             steps as u128,
-        );
-        self.lcg0 = lcg as u64;
-        self.lcg1 = (lcg >> 64) as u64;
+        ));
     }
 
     /// Returns current position in stream. The full state of the generator is (stream, position).
     pub fn position(&self) -> u128 {
-        crate::lcg::get_iterations(
-            self.multiplier_128(),
+        super::LCG_M65_1_POWERS.get_iterations(
             self.increment_128(),
             self.origin_128(),
             self.lcg_128(),
@@ -190,14 +325,11 @@ impl Krull64 {
 
     /// Sets position in stream.
     pub fn set_position(&mut self, position: u128) {
-        let lcg = crate::lcg::get_state(
-            self.multiplier_128(),
+        self.set_lcg_128(super::LCG_M65_1_POWERS.get_state(
             self.increment_128(),
             self.origin_128(),
             position,
-        );
-        self.lcg0 = lcg as u64;
-        self.lcg1 = (lcg >> 64) as u64;
+        ));
     }
 
     /// Resets stream position to 0. Equivalent to set_position(0).
@@ -218,9 +350,130 @@ impl Krull64 {
         self.stream = stream;
         self.reset();
     }
+
+    /// Returns an iterator over the outputs at the given range of positions
+    /// in the current stream. The iterator is double-ended and
+    /// it skips items with `nth` and `nth_back` in logarithmic time.
+    pub fn outputs(&self, positions: Range<u128>) -> Outputs<Krull64> {
+        Outputs::new(self, positions)
+    }
+
+    /// Returns lane `lane` of `lanes` interleaved substreams, starting from the current position.
+    /// The lane yields the outputs of calls number lane + 1, lane + 1 + lanes, ...
+    /// of `step()`, in constant time per output. Merged round-robin,
+    /// the lanes reproduce the serial stream exactly.
+    pub fn leapfrog(&self, lanes: u128, lane: u128) -> Leapfrog<Krull64> {
+        Leapfrog::new(self, lanes, lane)
+    }
+
+    /// Partitions the stream, starting from the current position, into `n` equal, disjoint blocks
+    /// of floor(2**128 / n) outputs each. Returns an iterator over generators
+    /// bounded to the blocks, which behave according to `overrun` when they run out.
+    /// Block i yields the outputs of calls number i * len + 1 to (i + 1) * len of `step()`.
+    pub fn partition(&self, n: u128, overrun: Overrun) -> Partition<Krull64> {
+        Partition::new(self, n, overrun)
+    }
+
+    /// Reserves the next `n` outputs for a subsystem and advances past them.
+    /// The returned generator is bounded to the reserved outputs:
+    /// it reports an error from `try_fill_bytes` when exhausted
+    /// and panics if stepped further.
+    pub fn reserve(&mut self, n: u128) -> Budgeted<Krull64> {
+        Block::reserve(self, n)
+    }
+
+    /// Creates a new Krull64 RNG on the stream obtained by hashing a byte key,
+    /// such as a path like "world/terrain/chunk(12,-5)". Position is set to 0.
+    /// The stream is the `hash_key_64` of the key, which is stable
+    /// within a `KEY_HASH_VERSION`. Equal to `Krull64::new().derive(key)`.
+    pub fn from_key(key: &[u8]) -> Self {
+        Krull64::from_64(hash_key_64(0, key))
+    }
+
+    /// Derives a generator for a named subsystem on the stream obtained by hashing a byte key
+    /// keyed with the current stream. Position is set to 0. The result does not depend
+    /// on the current position, so nested subsystems can derive reproducible,
+    /// independent streams from their parents without a central registry.
+    pub fn derive(&self, key: &[u8]) -> Self {
+        Krull64::from_64(hash_key_64(self.stream(), key))
+    }
+
+    /// Derives a child generator from the next output and advances by one step.
+    /// The child is on a fresh stream, which is a bijective hash of the output, at position 0.
+    /// Forking is deterministic: the same parent always yields the same tree of generators.
+    ///
+    /// Child streams are effectively random 64-bit numbers. In a fork tree
+    /// with F generators in total, the probability that two of them share a stream,
+    /// and so produce identical sequences, is at most about F**2 / 2**65,
+    /// for example, 2**-25 for a million generators. Use Krull65 for larger trees.
+    pub fn fork(&mut self) -> Krull64 {
+        Krull64::from_64(hash(self.step() ^ FORK_KEY))
+    }
 }
 
-use super::{Error, RngCore, SeedableRng};
+use super::{Error, RandomAccess, RngCore, SeedableRng};
+
+impl RandomAccess for Krull64 {
+    type Stream = u64;
+
+    #[inline]
+    fn get(&self) -> u64 {
+        Krull64::get(self)
+    }
+
+    #[inline]
+    fn step(&mut self) -> u64 {
+        Krull64::step(self)
+    }
+
+    #[inline]
+    fn step_back(&mut self) -> u64 {
+        Krull64::step_back(self)
+    }
+
+    fn jump(&mut self, steps: i128) {
+        Krull64::jump(self, steps)
+    }
+
+    fn position(&self) -> u128 {
+        Krull64::position(self)
+    }
+
+    fn set_position(&mut self, position: u128) {
+        Krull64::set_position(self, position)
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        Krull64::reset(self)
+    }
+
+    fn stream(&self) -> u64 {
+        Krull64::stream(self)
+    }
+
+    fn set_stream(&mut self, stream: u64) {
+        Krull64::set_stream(self, stream)
+    }
+
+    fn output_at(stream: u64, position: u128) -> u64 {
+        Krull64::output_at(stream, position)
+    }
+}
+
+impl Leap for Krull64 {
+    type Stride = Affine<u128>;
+
+    fn stride(&self, steps: u128) -> Affine<u128> {
+        Affine::from(super::LCG_M65_1_POWERS.get_jump(self.increment_128(), steps))
+    }
+
+    #[inline]
+    fn leap(&mut self, stride: &Affine<u128>) -> u64 {
+        self.set_lcg_128(stride.apply(self.lcg_128()));
+        self.get()
+    }
+}
 
 impl RngCore for Krull64 {
     fn next_u32(&mut self) -> u32 {
@@ -232,14 +485,15 @@ impl RngCore for Krull64 {
     }
 
     fn fill_bytes(&mut self, dest: &mut [u8]) {
-        let bytes = dest.len();
-        let mut i = 0;
-        while i < bytes {
+        let mut chunks = dest.chunks_exact_mut(8);
+        // Always use Little-Endian.
+        for chunk in &mut chunks {
+            chunk.copy_from_slice(&self.step().to_le_bytes());
+        }
+        let rest = chunks.into_remainder();
+        if !rest.is_empty() {
             let x = self.step();
-            let j = bytes.min(i + 8);
-            // Always use Little-Endian.
-            dest[i..j].copy_from_slice(&x.to_le_bytes()[0..(j - i)]);
-            i = j;
+            rest.copy_from_slice(&x.to_le_bytes()[0..rest.len()]);
         }
     }
 
@@ -289,6 +543,11 @@ mod tests {
         for x in krull64_expected {
             assert_eq!(x, krull64.next_u64());
         }
+        const OUTPUT_1: u64 = Krull64::output_at(0, 1);
+        assert_eq!(krull64_expected[0], OUTPUT_1);
+        for (i, x) in krull64_expected.into_iter().enumerate() {
+            assert_eq!(x, Krull64::output_at(0, i as u128 + 1));
+        }
 
         let mut r: u128 = 0;
         let mut rnd = || -> u128 {
@@ -314,6 +573,8 @@ mod tests {
             krull2.set_position(pos2);
             assert_eq!(pos1, krull1.position());
             assert_eq!(pos2, krull2.position());
+            assert_eq!(Krull64::output_at(seed, pos1), krull1.get());
+            assert_eq!(Krull64::output_at(seed, pos2), krull2.get());
             krull1.jump((pos2 - pos1) as i128);
             assert_eq!(pos2, krull1.position());
             assert_eq!(krull1.next_u64(), krull2.next_u64());
@@ -325,10 +586,19 @@ mod tests {
             assert_eq!(pos1, krull1.position());
 
             let n = 1 + (rnd() & 0x3ff);
-            for _ in 0..n {
-                krull1.next_u64();
+            let mut outputs = [0u64; 0x400];
+            for x in outputs[0..n as usize].iter_mut() {
+                *x = krull1.next_u64();
             }
             assert_eq!(pos1 + n, krull1.position());
+            for x in outputs[0..n as usize].iter().rev() {
+                assert_eq!(*x, krull1.prev_u64());
+            }
+            assert_eq!(pos1, krull1.position());
+            krull1.step();
+            krull1.step_back();
+            assert_eq!(pos1, krull1.position());
+            krull1.jump(n as i128);
 
             assert_eq!(seed, krull1.stream());
 
@@ -347,6 +617,62 @@ mod tests {
                 .iter()
                 .zip(buffer2[0..bytes as usize].iter())
                 .all(|(x, y)| x == y));
+
+            // Long buffers are filled in interleaved lanes.
+            let words = (rnd() & 0x1ff) as usize;
+            let mut buffer1 = [0u64; 0x200];
+            let mut buffer2 = [0u32; 0x200];
+            krull1.set_position(pos1);
+            krull1.fill_u64(&mut buffer1[0..words]);
+            krull1.fill_u32(&mut buffer2[0..words]);
+            assert_eq!(pos1 + 2 * words as u128, krull1.position());
+            krull1.set_position(pos1);
+            for x in buffer1[0..words].iter() {
+                assert_eq!(*x, krull1.next_u64());
+            }
+            for x in buffer2[0..words].iter() {
+                assert_eq!(*x, krull1.next_u32());
+            }
+        }
+    }
+
+    #[test]
+    pub fn fork_tests() {
+        // Build a binary fork tree in breadth-first order.
+        const N: usize = 1 << 12;
+        let mut tree = [(); N].map(|_| Krull64::new());
+        let mut streams = [0u64; N];
+        for i in 1..N {
+            let parent = &mut tree[(i - 1) >> 1];
+            let position = parent.position();
+            let stream = parent.stream();
+            tree[i] = parent.fork();
+            assert_eq!(position + 1, tree[(i - 1) >> 1].position());
+            assert_eq!(
+                hash(Krull64::output_at(stream, position + 1) ^ FORK_KEY),
+                tree[i].stream()
+            );
+            assert_eq!(0, tree[i].position());
+            streams[i] = tree[i].stream();
+        }
+        assert_eq!(streams[1], Krull64::new().fork().stream());
+        streams.sort_unstable();
+        assert!(streams.windows(2).all(|w| w[0] != w[1]));
+
+        // Stream collisions follow the birthday bound. Truncated to 20 bits,
+        // N streams are expected to contain about N**2 / 2**21 = 8 colliding pairs.
+        let mut truncated = streams.map(|stream| stream & 0xfffff);
+        truncated.sort_unstable();
+        let mut pairs = 0;
+        let mut run = 1;
+        for i in 1..N {
+            if truncated[i] == truncated[i - 1] {
+                pairs += run;
+                run += 1;
+            } else {
+                run = 1;
+            }
         }
+        assert!((1..=20).contains(&pairs));
     }
 }
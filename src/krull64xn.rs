@@ -0,0 +1,146 @@
+use super::krull64::{hash, step_lcg};
+use super::Krull64;
+
+// Krull64xN runs N Krull64 generators side by side in struct-of-arrays layout.
+// The step is written as plain loops over the lanes
+// to give the compiler a chance to vectorize it.
+
+/// N Krull64 RNGs stepped in unison. Lane i is bit-identical
+/// to a Krull64 RNG with the same stream and position.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Krull64xN<const N: usize> {
+    /// LCG state low bits.
+    lcg0: [u64; N],
+    /// LCG state high bits.
+    lcg1: [u64; N],
+    /// Stream numbers.
+    stream: [u64; N],
+}
+
+/// Four Krull64 RNGs stepped in unison.
+pub type Krull64x4 = Krull64xN<4>;
+
+/// Eight Krull64 RNGs stepped in unison.
+pub type Krull64x8 = Krull64xN<8>;
+
+impl<const N: usize> Krull64xN<N> {
+    /// Creates N Krull64 RNGs with the given streams.
+    /// Lane i is identical to `Krull64::from_64(streams[i])`.
+    pub fn from_streams(streams: [u64; N]) -> Self {
+        Self::from_lanes(&streams.map(Krull64::from_64))
+    }
+
+    /// Creates N Krull64 RNGs with the same stream and position as the given RNGs.
+    pub fn from_lanes(lanes: &[Krull64; N]) -> Self {
+        Krull64xN {
+            lcg0: core::array::from_fn(|i| lanes[i].lcg0),
+            lcg1: core::array::from_fn(|i| lanes[i].lcg1),
+            stream: core::array::from_fn(|i| lanes[i].stream),
+        }
+    }
+
+    /// Returns lane i as a Krull64 RNG.
+    pub fn lane(&self, i: usize) -> Krull64 {
+        Krull64 {
+            lcg0: self.lcg0[i],
+            lcg1: self.lcg1[i],
+            stream: self.stream[i],
+        }
+    }
+
+    /// Replaces lane i with the given Krull64 RNG.
+    pub fn set_lane(&mut self, i: usize, krull: &Krull64) {
+        self.lcg0[i] = krull.lcg0;
+        self.lcg1[i] = krull.lcg1;
+        self.stream[i] = krull.stream;
+    }
+
+    /// Generates the next 64-bit random number in each lane.
+    #[inline]
+    pub fn step(&mut self) -> [u64; N] {
+        for ((lcg0, lcg1), stream) in self
+            .lcg0
+            .iter_mut()
+            .zip(self.lcg1.iter_mut())
+            .zip(self.stream.iter())
+        {
+            (*lcg0, *lcg1) = step_lcg(*lcg0, *lcg1, *stream);
+        }
+        self.get()
+    }
+
+    /// Returns the current 64-bit output of each lane.
+    #[inline]
+    pub fn get(&self) -> [u64; N] {
+        self.lcg1.map(hash)
+    }
+
+    /// Returns the stream of each lane.
+    #[inline]
+    pub fn streams(&self) -> [u64; N] {
+        self.stream
+    }
+
+    /// Jumps all lanes forward (if steps > 0) or backward (if steps < 0)
+    /// or does nothing (if steps = 0).
+    pub fn jump(&mut self, steps: i128) {
+        for i in 0..N {
+            let mut krull = self.lane(i);
+            krull.jump(steps);
+            self.set_lane(i, &krull);
+        }
+    }
+
+    /// Sets position of all lanes.
+    pub fn set_position(&mut self, position: u128) {
+        for i in 0..N {
+            let mut krull = self.lane(i);
+            krull.set_position(position);
+            self.set_lane(i, &krull);
+        }
+    }
+
+    /// Resets position of all lanes to 0. Equivalent to set_position(0).
+    pub fn reset(&mut self) {
+        for i in 0..N {
+            let mut krull = self.lane(i);
+            krull.reset();
+            self.set_lane(i, &krull);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn run_tests() {
+        let streams = [0, 1, 2, 0xffff_ffff_ffff_ffff, 1 << 32, 5, 6, 7];
+        let mut krulls = streams.map(Krull64::from_64);
+        let mut krullx = Krull64x8::from_streams(streams);
+        assert_eq!(streams, krullx.streams());
+        for _ in 0..0x100 {
+            assert_eq!(krulls.each_mut().map(Krull64::step), krullx.step());
+        }
+        assert_eq!(krullx, Krull64x8::from_lanes(&krulls));
+
+        krullx.jump(-0x80);
+        krullx.set_lane(3, &Krull64::from_64(3));
+        krulls[3] = Krull64::from_64(3);
+        for (i, krull) in krulls.iter_mut().enumerate() {
+            if i != 3 {
+                krull.jump(-0x80);
+            }
+            assert_eq!(*krull, krullx.lane(i));
+        }
+
+        krullx.set_position(1 << 100);
+        krullx.step();
+        for (i, stream) in krullx.streams().into_iter().enumerate() {
+            assert_eq!(Krull64::output_at(stream, (1 << 100) + 1), krullx.get()[i]);
+        }
+        krullx.reset();
+        assert_eq!(Krull64x8::from_streams(krullx.streams()), krullx);
+    }
+}
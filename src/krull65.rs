@@ -1,3 +1,9 @@
+use super::hash_key_128;
+use super::krull64::{hash, FORK_KEY};
+use super::lattice_position;
+use super::lcg::Affine;
+use super::{Block, Budgeted, Leap, Leapfrog, Outputs, Overrun, Partition};
+use core::ops::Range;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use wrapping_arithmetic::wrappit;
@@ -28,54 +34,118 @@ pub struct Krull65 {
 }
 
 #[inline]
-fn origin_a0() -> u64 {
+const fn origin_a0() -> u64 {
     0
 }
 
 #[inline]
-fn origin_a_128() -> u128 {
+const fn origin_a_128() -> u128 {
     origin_a0() as u128
 }
 
 #[inline]
-fn origin_b0() -> u64 {
+const fn origin_b0() -> u64 {
     1
 }
 
 #[inline]
-fn origin_b_128() -> u128 {
+const fn origin_b_128() -> u128 {
     origin_b0() as u128
 }
 
+#[inline]
+const fn increment_a_128(c1: u64) -> u128 {
+    ((c1 as u128) << 1) ^ super::LCG_M128_1
+}
+
+#[inline]
+const fn increment_b_128(c1: u64) -> u128 {
+    ((c1 as u128) << 1) ^ 1
+}
+
+/// Inverse of LCG A multiplier modulo 2**128.
+const MULTIPLIER_A_INVERSE: u128 = crate::lcg::get_inverse_128(super::LCG_M65_1);
+
+/// Inverse of LCG B multiplier modulo 2**128.
+const MULTIPLIER_B_INVERSE: u128 = crate::lcg::get_inverse_128(super::LCG_M65_4);
+
+/// Returns the high 64 bits of stream constant C.
+#[inline]
+const fn stream_c1(stream: u128) -> u64 {
+    // This transformation enhances diversity of nearby streams.
+    (stream ^ (stream >> 64)) as u64
+}
+
+/// Output function of Krull65 from the high bits of LCGs A and B.
+#[wrappit]
+#[inline]
+const fn output(a1: u64, b1: u64) -> u64 {
+    // Krull65 algorithm consists of two 128-bit LCGs advancing in synchrony.
+    // The LCGs A and B realize two cycles of length 2**128,
+    // with constants determined from high 64 bits of C, the stream.
+    // Low 64 bits of C are chosen by positioning B against A.
+    //
+    // As our starting point, we take the XOR of some high quality bits from A and B.
+    // Choose high 64 bits from B and A.
+    // As we're mixing different bits of the LCGs together,
+    // and the rest of the pipeline is bijective, this guarantees
+    // equidistribution with each 64-bit output appearing 2**64 times in each stream.
+    //
+    let x = b1 ^ (a1 << 32) ^ (a1 >> 32);
+
+    // The signal is already quite high quality here, as the minimum periodicity
+    // left in the bits is 2**96 samples.
+    //
+    // We can examine our chosen worst case of the user XORing two streams X and Y
+    // at the worst possible location with C being identical.
+    // At this point in the pipeline, pairwise correlations between X and Y
+    // can be measured easily, as they are just autocorrelations of B: A is identical.
+    // So the sequence X XOR Y is the XOR of B with a lagged copy of itself.
+    //
+    // Fortunately, only in a vanishing fraction of cases does the output hash
+    // have to do significant work to remove the pairwise correlations.
+    // The level of correlation is indicated by the lowest differing bit in C.
+    // In the next table we can see how hashing improves the result
+    // with some statistical failures of X XOR Y investigated with PractRand.
+    //
+    // Identical bits       31     63     95     127
+    // ---------------------------------------------
+    // No hashing         256MB    1MB    1MB    1MB
+    // 1 round             >1TB   32GB    1MB    1MB
+    // 2 rounds             ?      ?    ~64TB    1MB
+    // 3 rounds             ?      ?      ?     >1TB
+    //
+    // We have cordoned off 64 bits of the theoretical 128-bit phase difference
+    // to avoid extreme correlations, leaving our worst case at 63 identical bits.
+    // At that level of correlation, we need a second round of hashing
+    // to purify streams pairwise. The output hash is intended to also
+    // pass tests as an indexed RNG.
+    //
+    let x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9; // round 1
+    let x = (x ^ (x >> 27)) * 0x94d049bb133111eb; // round 2
+    let x = (x ^ (x >> 31)) * 0xd6e8feb86659fd93; // round 3
+    x ^ (x >> 32)
+}
+
 impl Krull65 {
     #[inline]
     fn multiplier_a(&self) -> u64 {
         super::LCG_M65_1 as u64
     }
 
-    #[inline]
-    fn multiplier_a_128(&self) -> u128 {
-        super::LCG_M65_1
-    }
-
     #[inline]
     fn multiplier_b(&self) -> u64 {
         super::LCG_M65_4 as u64
     }
 
-    #[inline]
-    fn multiplier_b_128(&self) -> u128 {
-        super::LCG_M65_4
-    }
-
     #[inline]
     fn increment_a_128(&self) -> u128 {
-        ((self.c1 as u128) << 1) ^ super::LCG_M128_1
+        increment_a_128(self.c1)
     }
 
     #[inline]
     fn increment_b_128(&self) -> u128 {
-        ((self.c1 as u128) << 1) ^ 1
+        increment_b_128(self.c1)
     }
 
     #[inline]
@@ -116,61 +186,94 @@ impl Krull65 {
         self.get()
     }
 
+    /// Steps back to the previous position and returns the 64-bit output there.
+    /// This is the inverse of `step()` and takes about as long.
+    #[wrappit]
+    #[inline]
+    pub fn step_back(&mut self) -> u64 {
+        // The inverse LCG iteration is state <- (state - p) * m^-1.
+        self.set_a_128((self.a_128() - self.increment_a_128()) * MULTIPLIER_A_INVERSE);
+        self.set_b_128((self.b_128() - self.increment_b_128()) * MULTIPLIER_B_INVERSE);
+        self.get()
+    }
+
+    /// Returns the current 64-bit output and steps back to the previous position.
+    /// Repeated calls return the numbers generated by `step()` in reverse order.
+    #[inline]
+    pub fn prev_u64(&mut self) -> u64 {
+        let x = self.get();
+        self.step_back();
+        x
+    }
+
     /// Generates the next 128-bit random number.
     #[inline]
     pub fn step_128(&mut self) -> u128 {
         self.step() as u128 | ((self.step() as u128) << 64)
     }
 
+    /// Fills `dest` with 64-bit random numbers.
+    /// The result is identical to calling `step()` for each element in turn.
+    pub fn fill_u64(&mut self, dest: &mut [u64]) {
+        // Unlike in Krull64, a serial loop is already bound by multiplier throughput:
+        // the two LCG steps and the output hash take 7 multiplies per output.
+        // Jumped copies of the LCGs, as in Krull64::fill_u64, measured 7-10% slower here
+        // as their state does not fit in registers (see the serial benchmark).
+        for x in dest.iter_mut() {
+            *x = self.step();
+        }
+    }
+
+    /// Fills `dest` with 32-bit random numbers.
+    /// The result is identical to calling `next_u32()` for each element in turn.
+    pub fn fill_u32(&mut self, dest: &mut [u32]) {
+        for x in dest.iter_mut() {
+            *x = self.step() as u32;
+        }
+    }
+
     /// Returns the current 64-bit output.
-    #[wrappit]
     #[inline]
     pub fn get(&self) -> u64 {
-        // Krull65 algorithm consists of two 128-bit LCGs advancing in synchrony.
-        // The LCGs A and B realize two cycles of length 2**128,
-        // with constants determined from high 64 bits of C, the stream.
-        // Low 64 bits of C are chosen by positioning B against A.
-        //
-        // As our starting point, we take the XOR of some high quality bits from A and B.
-        // Choose high 64 bits from B and A.
-        // As we're mixing different bits of the LCGs together,
-        // and the rest of the pipeline is bijective, this guarantees
-        // equidistribution with each 64-bit output appearing 2**64 times in each stream.
-        //
-        let x = self.b1 ^ (self.a1 << 32) ^ (self.a1 >> 32);
-
-        // The signal is already quite high quality here, as the minimum periodicity
-        // left in the bits is 2**96 samples.
-        //
-        // We can examine our chosen worst case of the user XORing two streams X and Y
-        // at the worst possible location with C being identical.
-        // At this point in the pipeline, pairwise correlations between X and Y
-        // can be measured easily, as they are just autocorrelations of B: A is identical.
-        // So the sequence X XOR Y is the XOR of B with a lagged copy of itself.
-        //
-        // Fortunately, only in a vanishing fraction of cases does the output hash
-        // have to do significant work to remove the pairwise correlations.
-        // The level of correlation is indicated by the lowest differing bit in C.
-        // In the next table we can see how hashing improves the result
-        // with some statistical failures of X XOR Y investigated with PractRand.
-        //
-        // Identical bits       31     63     95     127
-        // ---------------------------------------------
-        // No hashing         256MB    1MB    1MB    1MB
-        // 1 round             >1TB   32GB    1MB    1MB
-        // 2 rounds             ?      ?    ~64TB    1MB
-        // 3 rounds             ?      ?      ?     >1TB
-        //
-        // We have cordoned off 64 bits of the theoretical 128-bit phase difference
-        // to avoid extreme correlations, leaving our worst case at 63 identical bits.
-        // At that level of correlation, we need a second round of hashing
-        // to purify streams pairwise. The output hash is intended to also
-        // pass tests as an indexed RNG.
-        //
-        let x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9; // round 1
-        let x = (x ^ (x >> 27)) * 0x94d049bb133111eb; // round 2
-        let x = (x ^ (x >> 31)) * 0xd6e8feb86659fd93; // round 3
-        x ^ (x >> 32)
+        output(self.a1, self.b1)
+    }
+
+    /// Returns the 64-bit output at the given position of the given stream
+    /// without constructing a generator. This is a pure function:
+    /// the result is always identical to that of `get()`
+    /// after `set_stream(stream)` and `set_position(position)`.
+    /// In particular, the nth call to `step()` from position 0
+    /// returns `output_at(stream, n)`.
+    pub const fn output_at(stream: u128, position: u128) -> u64 {
+        let c1 = stream_c1(stream);
+        let a = super::LCG_M65_1_POWERS.get_state(increment_a_128(c1), origin_a_128(), position);
+        // Low bits of stream are encoded as the phase difference (B - A).
+        let b = super::LCG_M65_4_POWERS.get_state(
+            increment_b_128(c1),
+            origin_b_128(),
+            position.wrapping_add((stream as u64) as u128),
+        );
+        output((a >> 64) as u64, (b >> 64) as u64)
+    }
+
+    /// Returns the 64-bit value of the lattice point (x, y) in the given stream.
+    /// Equal to `at_nd(stream, &[x, y])`. Each point has its own stream position.
+    pub const fn at_2d(stream: u128, x: i64, y: i64) -> u64 {
+        Self::at_nd(stream, &[x, y])
+    }
+
+    /// Returns the 64-bit value of the lattice point (x, y, z) in the given stream.
+    /// Equal to `at_nd(stream, &[x, y, z])`.
+    pub const fn at_3d(stream: u128, x: i64, y: i64, z: i64) -> u64 {
+        Self::at_nd(stream, &[x, y, z])
+    }
+
+    /// Returns the 64-bit value of a lattice point of any dimension in the given stream.
+    /// Coordinates are scrambled into a stream position with `lattice_position`,
+    /// so values show no correlations along axes or diagonals.
+    /// Lattices of different dimensions are independent.
+    pub const fn at_nd(stream: u128, coords: &[i64]) -> u64 {
+        Self::output_at(stream, lattice_position(coords))
     }
 
     /// Creates a new Krull65 RNG.
@@ -228,14 +331,12 @@ impl Krull65 {
     /// Jumps forward (if steps > 0) or backward (if steps < 0) or does nothing (if steps = 0).
     /// The stream wraps around, so signed steps can be interpreted as unsigned.
     pub fn jump(&mut self, steps: i128) {
-        self.set_a_128(crate::lcg::get_state(
-            self.multiplier_a_128(),
+        self.set_a_128(super::LCG_M65_1_POWERS.get_state(
             self.increment_a_128(),
             self.a_128(),
             steps as u128,
         ));
-        self.set_b_128(crate::lcg::get_state(
-            self.multiplier_b_128(),
+        self.set_b_128(super::LCG_M65_4_POWERS.get_state(
             self.increment_b_128(),
             self.b_128(),
             steps as u128,
@@ -245,12 +346,7 @@ impl Krull65 {
     /// Returns current position in stream. The full state of the generator is (stream, position).
     pub fn position(&self) -> u128 {
         // Position is encoded in A.
-        super::lcg::get_iterations(
-            self.multiplier_a_128(),
-            self.increment_a_128(),
-            origin_a_128(),
-            self.a_128(),
-        )
+        super::LCG_M65_1_POWERS.get_iterations(self.increment_a_128(), origin_a_128(), self.a_128())
     }
 
     /// Sets position in stream.
@@ -272,8 +368,7 @@ impl Krull65 {
     #[inline]
     pub fn stream(&self) -> u128 {
         let a_n = self.position();
-        let b_n = super::lcg::get_iterations(
-            self.multiplier_b_128(),
+        let b_n = super::LCG_M65_4_POWERS.get_iterations(
             self.increment_b_128(),
             origin_b_128(),
             self.b_128(),
@@ -285,19 +380,144 @@ impl Krull65 {
 
     /// Sets stream and initializes position to 0.
     pub fn set_stream(&mut self, stream: u128) {
-        // This transformation enhances diversity of nearby streams.
-        self.c1 = (stream ^ (stream >> 64)) as u64;
+        self.c1 = stream_c1(stream);
         self.reset();
-        self.set_b_128(crate::lcg::get_state(
-            self.multiplier_b_128(),
+        self.set_b_128(super::LCG_M65_4_POWERS.get_state(
             self.increment_b_128(),
             origin_b_128(),
             (stream as u64) as u128,
         ));
     }
+
+    /// Returns an iterator over the outputs at the given range of positions
+    /// in the current stream. The iterator is double-ended and
+    /// it skips items with `nth` and `nth_back` in logarithmic time.
+    pub fn outputs(&self, positions: Range<u128>) -> Outputs<Krull65> {
+        Outputs::new(self, positions)
+    }
+
+    /// Returns lane `lane` of `lanes` interleaved substreams, starting from the current position.
+    /// The lane yields the outputs of calls number lane + 1, lane + 1 + lanes, ...
+    /// of `step()`, in constant time per output. Merged round-robin,
+    /// the lanes reproduce the serial stream exactly.
+    pub fn leapfrog(&self, lanes: u128, lane: u128) -> Leapfrog<Krull65> {
+        Leapfrog::new(self, lanes, lane)
+    }
+
+    /// Partitions the stream, starting from the current position, into `n` equal, disjoint blocks
+    /// of floor(2**128 / n) outputs each. Returns an iterator over generators
+    /// bounded to the blocks, which behave according to `overrun` when they run out.
+    /// Block i yields the outputs of calls number i * len + 1 to (i + 1) * len of `step()`.
+    pub fn partition(&self, n: u128, overrun: Overrun) -> Partition<Krull65> {
+        Partition::new(self, n, overrun)
+    }
+
+    /// Reserves the next `n` outputs for a subsystem and advances past them.
+    /// The returned generator is bounded to the reserved outputs:
+    /// it reports an error from `try_fill_bytes` when exhausted
+    /// and panics if stepped further.
+    pub fn reserve(&mut self, n: u128) -> Budgeted<Krull65> {
+        Block::reserve(self, n)
+    }
+
+    /// Creates a new Krull65 RNG on the stream obtained by hashing a byte key,
+    /// such as a path like "world/terrain/chunk(12,-5)". Position is set to 0.
+    /// The stream is the `hash_key_128` of the key, which is stable
+    /// within a `KEY_HASH_VERSION`. Equal to `Krull65::new().derive(key)`.
+    pub fn from_key(key: &[u8]) -> Self {
+        Krull65::from_128(hash_key_128(0, key))
+    }
+
+    /// Derives a generator for a named subsystem on the stream obtained by hashing a byte key
+    /// keyed with the current stream. Position is set to 0. The result does not depend
+    /// on the current position, so nested subsystems can derive reproducible,
+    /// independent streams from their parents without a central registry.
+    pub fn derive(&self, key: &[u8]) -> Self {
+        Krull65::from_128(hash_key_128(self.stream(), key))
+    }
+
+    /// Derives a child generator from the next two outputs and advances by two steps.
+    /// The child is on a fresh stream, which is a bijective hash of the outputs, at position 0.
+    /// Forking is deterministic: the same parent always yields the same tree of generators.
+    ///
+    /// Child streams are effectively random 128-bit numbers. Even a tree of 2**40 generators
+    /// has a probability of a shared stream of only about 2**-49, so fork trees
+    /// can be arbitrarily deep and wide.
+    pub fn fork(&mut self) -> Krull65 {
+        let x = self.step_128();
+        let stream =
+            hash(x as u64 ^ FORK_KEY) as u128 | ((hash((x >> 64) as u64 ^ FORK_KEY) as u128) << 64);
+        Krull65::from_128(stream)
+    }
+}
+
+use super::{Error, RandomAccess, RngCore, SeedableRng};
+
+impl RandomAccess for Krull65 {
+    type Stream = u128;
+
+    #[inline]
+    fn get(&self) -> u64 {
+        Krull65::get(self)
+    }
+
+    #[inline]
+    fn step(&mut self) -> u64 {
+        Krull65::step(self)
+    }
+
+    #[inline]
+    fn step_back(&mut self) -> u64 {
+        Krull65::step_back(self)
+    }
+
+    fn jump(&mut self, steps: i128) {
+        Krull65::jump(self, steps)
+    }
+
+    fn position(&self) -> u128 {
+        Krull65::position(self)
+    }
+
+    fn set_position(&mut self, position: u128) {
+        Krull65::set_position(self, position)
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        Krull65::reset(self)
+    }
+
+    fn stream(&self) -> u128 {
+        Krull65::stream(self)
+    }
+
+    fn set_stream(&mut self, stream: u128) {
+        Krull65::set_stream(self, stream)
+    }
+
+    fn output_at(stream: u128, position: u128) -> u64 {
+        Krull65::output_at(stream, position)
+    }
 }
 
-use super::{Error, RngCore, SeedableRng};
+impl Leap for Krull65 {
+    type Stride = [Affine<u128>; 2];
+
+    fn stride(&self, steps: u128) -> [Affine<u128>; 2] {
+        [
+            Affine::from(super::LCG_M65_1_POWERS.get_jump(self.increment_a_128(), steps)),
+            Affine::from(super::LCG_M65_4_POWERS.get_jump(self.increment_b_128(), steps)),
+        ]
+    }
+
+    #[inline]
+    fn leap(&mut self, stride: &[Affine<u128>; 2]) -> u64 {
+        self.set_a_128(stride[0].apply(self.a_128()));
+        self.set_b_128(stride[1].apply(self.b_128()));
+        self.get()
+    }
+}
 
 impl RngCore for Krull65 {
     fn next_u32(&mut self) -> u32 {
@@ -309,14 +529,15 @@ impl RngCore for Krull65 {
     }
 
     fn fill_bytes(&mut self, dest: &mut [u8]) {
-        let bytes = dest.len();
-        let mut i = 0;
-        while i < bytes {
+        let mut chunks = dest.chunks_exact_mut(8);
+        // Always use Little-Endian.
+        for chunk in &mut chunks {
+            chunk.copy_from_slice(&self.step().to_le_bytes());
+        }
+        let rest = chunks.into_remainder();
+        if !rest.is_empty() {
             let x = self.step();
-            let j = bytes.min(i + 8);
-            // Always use Little-Endian.
-            dest[i..j].copy_from_slice(&x.to_le_bytes()[0..(j - i)]);
-            i = j;
+            rest.copy_from_slice(&x.to_le_bytes()[0..rest.len()]);
         }
     }
 
@@ -372,6 +593,11 @@ mod tests {
         for x in krull65_expected {
             assert_eq!(x, krull65.next_u64());
         }
+        const OUTPUT_1: u64 = Krull65::output_at(0, 1);
+        assert_eq!(krull65_expected[0], OUTPUT_1);
+        for (i, x) in krull65_expected.into_iter().enumerate() {
+            assert_eq!(x, Krull65::output_at(0, i as u128 + 1));
+        }
 
         let mut r: u128 = 0;
         let mut rnd = || -> u128 {
@@ -397,6 +623,8 @@ mod tests {
             krull2.set_position(pos2);
             assert_eq!(pos1, krull1.position());
             assert_eq!(pos2, krull2.position());
+            assert_eq!(Krull65::output_at(seed, pos1), krull1.get());
+            assert_eq!(Krull65::output_at(seed, pos2), krull2.get());
             krull1.jump((pos2 - pos1) as i128);
             assert_eq!(pos2, krull1.position());
             assert_eq!(krull1.next_u64(), krull2.next_u64());
@@ -409,10 +637,19 @@ mod tests {
             assert_eq!(pos1, krull1.position());
 
             let n = 1 + (rnd() & 0x3ff);
-            for _ in 0..n {
-                krull1.next_u64();
+            let mut outputs = [0u64; 0x400];
+            for x in outputs[0..n as usize].iter_mut() {
+                *x = krull1.next_u64();
             }
             assert_eq!(pos1 + n, krull1.position());
+            for x in outputs[0..n as usize].iter().rev() {
+                assert_eq!(*x, krull1.prev_u64());
+            }
+            assert_eq!(pos1, krull1.position());
+            krull1.step();
+            krull1.step_back();
+            assert_eq!(pos1, krull1.position());
+            krull1.jump(n as i128);
 
             assert_eq!(seed, krull1.stream());
 
@@ -431,6 +668,47 @@ mod tests {
                 .iter()
                 .zip(buffer2[0..bytes as usize].iter())
                 .all(|(x, y)| x == y));
+
+            let words = (rnd() & 0x3f) as usize;
+            let mut buffer1 = [0u64; 0x40];
+            let mut buffer2 = [0u32; 0x40];
+            krull1.set_position(pos1);
+            krull1.fill_u64(&mut buffer1[0..words]);
+            krull1.fill_u32(&mut buffer2[0..words]);
+            assert_eq!(pos1 + 2 * words as u128, krull1.position());
+            krull1.set_position(pos1);
+            for x in buffer1[0..words].iter() {
+                assert_eq!(*x, krull1.next_u64());
+            }
+            for x in buffer2[0..words].iter() {
+                assert_eq!(*x, krull1.next_u32());
+            }
+        }
+    }
+
+    #[test]
+    pub fn fork_tests() {
+        // Build a binary fork tree in breadth-first order.
+        const N: usize = 1 << 10;
+        let mut tree = [(); N].map(|_| Krull65::new());
+        let mut streams = [0u128; N];
+        for i in 1..N {
+            let parent = &mut tree[(i - 1) >> 1];
+            let position = parent.position();
+            let stream = parent.stream();
+            tree[i] = parent.fork();
+            assert_eq!(position + 2, tree[(i - 1) >> 1].position());
+            let x0 = Krull65::output_at(stream, position + 1);
+            let x1 = Krull65::output_at(stream, position + 2);
+            assert_eq!(
+                ((hash(x1 ^ FORK_KEY) as u128) << 64) | hash(x0 ^ FORK_KEY) as u128,
+                tree[i].stream()
+            );
+            assert_eq!(0, tree[i].position());
+            streams[i] = tree[i].stream();
         }
+        assert_eq!(streams[1], Krull65::new().fork().stream());
+        streams.sort_unstable();
+        assert!(streams.windows(2).all(|w| w[0] != w[1]));
     }
 }
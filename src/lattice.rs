@@ -0,0 +1,132 @@
+use super::krull64::hash;
+
+// Lattice points are mapped to stream positions by absorbing the coordinates two at a time
+// into a 128-bit state, which is scrambled with a Feistel network built from the Krull64
+// output hash. The network is keyed with the number of dimensions. The output at the
+// position then goes through the usual output hash. As the Feistel network is a bijection,
+// points of up to 2 dimensions map to distinct positions. Scrambling hides the lattice
+// structure from the LCGs, whose states at positions a power of two apart are related.
+
+/// Round keys of the Feistel network.
+const ROUND_KEYS: [u64; 4] = [
+    0x9e3779b97f4a7c15,
+    0xc2b2ae3d27d4eb4f,
+    0x165667b19e3779f9,
+    0x27d4eb2f165667c5,
+];
+
+/// Scrambles the state (l, r) with a 4-round Feistel network.
+#[inline]
+const fn feistel(l: u64, r: u64, key: u64) -> (u64, u64) {
+    let l = l ^ hash(r ^ key ^ ROUND_KEYS[0]);
+    let r = r ^ hash(l ^ key ^ ROUND_KEYS[1]);
+    let l = l ^ hash(r ^ key ^ ROUND_KEYS[2]);
+    let r = r ^ hash(l ^ key ^ ROUND_KEYS[3]);
+    (l, r)
+}
+
+/// Returns the stream position of a lattice point.
+/// Points of up to 2 dimensions map to distinct positions.
+/// Points with a different number of dimensions map to unrelated positions.
+pub const fn lattice_position(coords: &[i64]) -> u128 {
+    let key = hash(coords.len() as u64 ^ ROUND_KEYS[0]);
+    let mut l = 0;
+    let mut r = 0;
+    let mut i = 0;
+    loop {
+        if i < coords.len() {
+            l ^= coords[i] as u64;
+        }
+        if i + 1 < coords.len() {
+            r ^= coords[i + 1] as u64;
+        }
+        (l, r) = feistel(l, r, key);
+        i += 2;
+        if i >= coords.len() {
+            break;
+        }
+    }
+    ((l as u128) << 64) | (r as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::math::sqrt;
+    use super::super::*;
+    use super::*;
+
+    /// Checks that neighbouring values of a lattice are uncorrelated.
+    /// `pairs` contains pairs of neighbouring values.
+    /// For each bit, counts how often the bit is equal in both values of a pair.
+    fn test_neighbours(pairs: &mut dyn Iterator<Item = (u64, u64)>) {
+        let mut agree = [0u64; 64];
+        let mut ones = [0u64; 64];
+        let mut n: u64 = 0;
+        for (x, y) in pairs {
+            let same = !(x ^ y);
+            for bit in 0..64 {
+                agree[bit] += (same >> bit) & 1;
+                ones[bit] += (x >> bit) & 1;
+            }
+            n += 1;
+        }
+        // Allow 6 standard deviations.
+        let limit = 3 * sqrt(n as f64) as u64;
+        for bit in 0..64 {
+            assert!(agree[bit].abs_diff(n / 2) < limit);
+            assert!(ones[bit].abs_diff(n / 2) < limit);
+        }
+        let total_agree: u64 = agree.iter().sum();
+        assert!(total_agree.abs_diff(n * 32) < 3 * sqrt((n * 64) as f64) as u64);
+    }
+
+    #[test]
+    pub fn run_tests() {
+        for coords in [[0i64, 0], [1, 0], [0, 1], [-1, 5], [i64::MIN, i64::MAX]] {
+            let [x, y] = coords;
+            assert_eq!(
+                Krull65::output_at(3, lattice_position(&coords)),
+                Krull65::at_2d(3, x, y)
+            );
+            assert_eq!(Krull65::at_2d(3, x, y), Krull65::at_nd(3, &coords));
+            assert_eq!(Krull64::at_2d(3, x, y), Krull64::at_nd(3, &coords));
+            assert_eq!(Krull65::at_3d(3, x, y, 1), Krull65::at_nd(3, &[x, y, 1]));
+            assert_eq!(Krull64::at_3d(3, x, y, 1), Krull64::at_nd(3, &[x, y, 1]));
+            assert_ne!(Krull65::at_2d(3, x, y), Krull65::at_2d(4, x, y));
+            assert_ne!(lattice_position(&[x]), lattice_position(&[x, 0]));
+            assert_ne!(lattice_position(&[x, y]), lattice_position(&[x, y, 0]));
+        }
+        const VALUE: u64 = Krull65::at_nd(1, &[1, 2, 3, 4, 5]);
+        assert_eq!(VALUE, Krull65::at_nd(1, &[1, 2, 3, 4, 5]));
+
+        // Neighbours along each axis and diagonal in 2 dimensions.
+        const W: i64 = 128;
+        for stream in [0, 1 << 100] {
+            let mut grid = [[0u64; W as usize]; W as usize];
+            for y in 0..W {
+                for x in 0..W {
+                    grid[y as usize][x as usize] = Krull65::at_2d(stream, x - W / 2, y - W / 2);
+                }
+            }
+            let at = |x: i64, y: i64| grid[y as usize][x as usize];
+            for (dx, dy) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+                test_neighbours(
+                    &mut (1..W - 1)
+                        .flat_map(|y| (0..W - 1).map(move |x| (x, y)))
+                        .map(|(x, y)| (at(x, y), at(x + dx, y + dy))),
+                );
+            }
+        }
+
+        // Neighbours along each axis in 3 dimensions.
+        const D: i64 = 24;
+        let at = |x, y, z| Krull64::at_3d(5, x, y, z);
+        for (dx, dy, dz) in [(1, 0, 0), (0, 1, 0), (0, 0, 1)] {
+            test_neighbours(
+                &mut (0..D * D * D)
+                    .map(|i| (i % D - D / 2, i / D % D - D / 2, i / D / D - D / 2))
+                    .map(|(x, y, z)| (at(x, y, z), at(x + dx, y + dy, z + dz))),
+            );
+        }
+    }
+}
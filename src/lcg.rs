@@ -1,4 +1,7 @@
+use super::{Error, RngCore};
 use core::ops::{Add, BitAnd, BitOr, BitXor, Mul, Not, Shl, Shr, Sub};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use wrapping_arithmetic::wrappit;
 
 // This module contains utility functions for working with
@@ -110,6 +113,346 @@ pub fn get_state<T: Int>(m: T, p: T, origin: T, iterations: T) -> T {
     state
 }
 
+/// LCG iteration is state <- state * m + p.
+/// Returns state after the specified number of iterations from the origin state.
+/// This is a `const` version of `get_state` for 128-bit LCGs.
+/// Assumes (m, p) is full period.
+#[wrappit]
+pub const fn get_state_128(m: u128, p: u128, origin: u128, iterations: u128) -> u128 {
+    let mut jump_m = m;
+    let mut jump_p = p;
+    let mut state = origin;
+    let mut ordinal = iterations;
+
+    while ordinal > 0 {
+        if ordinal & 1 == 1 {
+            state = state * jump_m + jump_p;
+        }
+        jump_p = (jump_m + 1) * jump_p;
+        jump_m *= jump_m;
+        ordinal >>= 1;
+    }
+    state
+}
+
+/// Returns the multiplicative inverse of odd m in wrapping arithmetic. Panics if m is even.
+#[wrappit]
+pub fn get_inverse<T: Int>(m: T) -> T {
+    assert!(m & T::one() == T::one(), "get_inverse: m is even");
+    // Newton's iteration doubles the number of correct low bits in each round.
+    // The initial guess m is correct to 3 bits for odd m, so 6 rounds suffice for 128 bits.
+    let two = T::one() + T::one();
+    let mut inverse = m;
+    for _ in 0..6 {
+        let correction = two - m * inverse;
+        inverse = inverse * correction;
+    }
+    inverse
+}
+
+/// Returns the multiplicative inverse of odd m modulo 2**128. Panics if m is even.
+/// Equal to `get_inverse`, but usable in constant expressions.
+#[wrappit]
+pub const fn get_inverse_128(m: u128) -> u128 {
+    assert!(m & 1 == 1, "get_inverse_128: m is even");
+    let two: u128 = 2;
+    let mut inverse = m;
+    let mut i: usize = 0;
+    while i < 6 {
+        let correction = two - m * inverse;
+        inverse *= correction;
+        i += 1;
+    }
+    inverse
+}
+
+/// Precomputed powers of a 128-bit LCG multiplier m for fast random access.
+/// LCG iteration is state <- state * m + p. A jump of 2**k steps
+/// is state <- state * m**(2**k) + p * (1 + m + m**2 + ... + m**(2**k - 1)).
+/// The table stores both factors, so a jump costs two multiplies per set bit in the step count.
+/// The methods assume (m, p) is full period.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PowerTable {
+    /// Entry k is m**(2**k).
+    multiplier: [u128; 128],
+    /// Entry k is 1 + m + m**2 + ... + m**(2**k - 1).
+    increment: [u128; 128],
+}
+
+impl PowerTable {
+    /// Builds the power table of multiplier m.
+    pub const fn new(m: u128) -> Self {
+        let mut table = PowerTable {
+            multiplier: [0; 128],
+            increment: [0; 128],
+        };
+        let mut jump_m = m;
+        let mut jump_c: u128 = 1;
+        let mut k = 0;
+        while k < 128 {
+            table.multiplier[k] = jump_m;
+            table.increment[k] = jump_c;
+            jump_c = jump_m.wrapping_add(1).wrapping_mul(jump_c);
+            jump_m = jump_m.wrapping_mul(jump_m);
+            k += 1;
+        }
+        table
+    }
+
+    /// Returns the multiplier m.
+    #[inline]
+    pub const fn multiplier(&self) -> u128 {
+        self.multiplier[0]
+    }
+
+    /// Returns the (m, p) pair that iterates by n steps at once. Equivalent to `get_jump`.
+    pub const fn get_jump(&self, p: u128, n: u128) -> (u128, u128) {
+        let mut jump_m: u128 = 1;
+        let mut jump_c: u128 = 0;
+        let mut ordinal = n;
+        while ordinal != 0 {
+            let k = ordinal.trailing_zeros() as usize;
+            jump_m = jump_m.wrapping_mul(self.multiplier[k]);
+            jump_c = jump_c
+                .wrapping_mul(self.multiplier[k])
+                .wrapping_add(self.increment[k]);
+            ordinal &= ordinal - 1;
+        }
+        (jump_m, jump_c.wrapping_mul(p))
+    }
+
+    /// Returns the number of iterations between origin state and the given state.
+    /// Equivalent to `get_iterations`.
+    pub const fn get_iterations(&self, p: u128, origin: u128, state: u128) -> u128 {
+        let mut ordinal: u128 = 0;
+        let mut address = origin;
+        let mut k = 0;
+        while address != state {
+            let bit = 1 << k;
+            if (bit & address) != (bit & state) {
+                address = address
+                    .wrapping_mul(self.multiplier[k])
+                    .wrapping_add(p.wrapping_mul(self.increment[k]));
+                ordinal |= bit;
+            }
+            k += 1;
+        }
+        ordinal
+    }
+
+    /// Returns state after the specified number of iterations from the origin state.
+    /// Equivalent to `get_state`.
+    pub const fn get_state(&self, p: u128, origin: u128, iterations: u128) -> u128 {
+        let mut state = origin;
+        let mut ordinal = iterations;
+        while ordinal != 0 {
+            let k = ordinal.trailing_zeros() as usize;
+            state = state
+                .wrapping_mul(self.multiplier[k])
+                .wrapping_add(p.wrapping_mul(self.increment[k]));
+            ordinal &= ordinal - 1;
+        }
+        state
+    }
+}
+
+/// Affine map x -> m * x + p in wrapping arithmetic.
+/// LCG iteration is an affine map, and so are jumps of any length.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Affine<T: Int> {
+    /// Multiplier.
+    pub m: T,
+    /// Increment.
+    pub p: T,
+}
+
+impl<T: Int> Affine<T> {
+    /// Creates the affine map x -> m * x + p.
+    #[inline]
+    pub fn new(m: T, p: T) -> Self {
+        Affine { m, p }
+    }
+
+    /// Returns the identity map.
+    #[inline]
+    pub fn identity() -> Self {
+        Affine::new(T::one(), T::zero())
+    }
+
+    /// Applies the map to x.
+    #[wrappit]
+    #[inline]
+    pub fn apply(&self, x: T) -> T {
+        self.m * x + self.p
+    }
+
+    /// Returns the composition x -> self(other(x)), that is, the map
+    /// that applies `other` first and then `self`. Also available as `self * other`.
+    #[wrappit]
+    #[inline]
+    pub fn compose(&self, other: &Affine<T>) -> Self {
+        Affine::new(self.m * other.m, self.m * other.p + self.p)
+    }
+
+    /// Returns the map applied n times.
+    pub fn pow(&self, n: T) -> Self {
+        let (m, p) = get_jump(self.m, self.p, n);
+        Affine::new(m, p)
+    }
+
+    /// Returns the inverse map. The multiplier must be odd.
+    #[wrappit]
+    pub fn inverse(&self) -> Self {
+        let m = get_inverse(self.m);
+        Affine::new(m, (T::zero() - self.p) * m)
+    }
+}
+
+impl<T: Int> Mul for Affine<T> {
+    type Output = Affine<T>;
+
+    /// Composition: `(f * g).apply(x)` equals `f.apply(g.apply(x))`.
+    #[inline]
+    fn mul(self, other: Affine<T>) -> Affine<T> {
+        self.compose(&other)
+    }
+}
+
+impl<T: Int> From<(T, T)> for Affine<T> {
+    /// Converts an (m, p) pair as returned by `get_jump`.
+    #[inline]
+    fn from((m, p): (T, T)) -> Self {
+        Affine::new(m, p)
+    }
+}
+
+/// Linear congruential generator. LCG iteration is state <- state * m + p.
+/// Random access methods assume (m, p) is full period,
+/// which is the case when m = 1 (mod 4) and p is odd.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Lcg<T: Int> {
+    /// LCG state.
+    state: T,
+    /// Multiplier m.
+    multiplier: T,
+    /// Increment p.
+    increment: T,
+}
+
+impl<T: Int> Lcg<T> {
+    /// Creates a new LCG with the given state, multiplier and increment.
+    pub fn new(state: T, multiplier: T, increment: T) -> Self {
+        Lcg {
+            state,
+            multiplier,
+            increment,
+        }
+    }
+
+    /// Returns current state.
+    #[inline]
+    pub fn state(&self) -> T {
+        self.state
+    }
+
+    /// Sets state.
+    #[inline]
+    pub fn set_state(&mut self, state: T) {
+        self.state = state;
+    }
+
+    /// Returns multiplier m.
+    #[inline]
+    pub fn multiplier(&self) -> T {
+        self.multiplier
+    }
+
+    /// Returns increment p.
+    #[inline]
+    pub fn increment(&self) -> T {
+        self.increment
+    }
+
+    /// Advances the LCG and returns the new state.
+    #[inline]
+    pub fn step(&mut self) -> T {
+        self.state = self
+            .state
+            .wrapping_mul(self.multiplier)
+            .wrapping_add(self.increment);
+        self.state
+    }
+
+    /// Jumps forward by the given number of steps.
+    /// The sequence wraps around, so a jump backward by n steps is a jump forward by 0 - n steps.
+    pub fn jump(&mut self, steps: T) {
+        self.state = get_state(self.multiplier, self.increment, self.state, steps);
+    }
+
+    /// Returns the affine map of a single step.
+    #[inline]
+    pub fn affine(&self) -> Affine<T> {
+        Affine::new(self.multiplier, self.increment)
+    }
+
+    /// Applies an affine map to the state. For example, if the map is
+    /// `self.affine().pow(n)`, this is a jump of n steps.
+    #[inline]
+    pub fn transform(&mut self, map: &Affine<T>) {
+        self.state = map.apply(self.state);
+    }
+
+    /// Returns the number of steps from the origin state to the current state.
+    pub fn position_from(&self, origin: T) -> T {
+        get_iterations(self.multiplier, self.increment, origin, self.state)
+    }
+
+    /// Returns the number of steps from the current state to the state of `other`.
+    /// Both LCGs must have the same multiplier and increment.
+    pub fn distance(&self, other: &Lcg<T>) -> T {
+        debug_assert!(self.multiplier == other.multiplier && self.increment == other.increment);
+        get_iterations(self.multiplier, self.increment, self.state, other.state)
+    }
+}
+
+// Low bits of LCG state are of poor quality, so we output the highest bits.
+macro_rules! impl_lcg_rng {
+    ( $($t:ty),* ) => {
+    $( impl RngCore for Lcg<$t> {
+        #[inline]
+        fn next_u32(&mut self) -> u32 {
+            (self.step() >> (<$t>::BITS - 32)) as u32
+        }
+
+        #[inline]
+        fn next_u64(&mut self) -> u64 {
+            (self.step() >> (<$t>::BITS - 64)) as u64
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            let mut chunks = dest.chunks_exact_mut(8);
+            // Always use Little-Endian.
+            for chunk in &mut chunks {
+                chunk.copy_from_slice(&self.next_u64().to_le_bytes());
+            }
+            let rest = chunks.into_remainder();
+            if !rest.is_empty() {
+                let x = self.next_u64();
+                rest.copy_from_slice(&x.to_le_bytes()[0..rest.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }) *
+    }
+}
+impl_lcg_rng! { u64, u128 }
+
 #[cfg(test)]
 mod tests {
     use super::super::*;
@@ -117,21 +460,53 @@ mod tests {
 
     #[test]
     pub fn run_tests() {
+        let mut lcg = Lcg::new(1u64, LCG_M64_1, 1);
+        let x = lcg.clone().step();
+        assert_eq!(LCG_M64_1 + 1, x);
+        assert_eq!(x, lcg.clone().next_u64());
+        assert_eq!((x >> 32) as u32, lcg.clone().next_u32());
+        let mut buffer = [0u8; 12];
+        lcg.fill_bytes(&mut buffer);
+        assert_eq!(x.to_le_bytes(), buffer[0..8]);
+        assert_eq!(2, lcg.position_from(1));
+
         let mut r: u128 = 0;
         let mut rnd = || -> u128 {
             r = r.wrapping_mul(LCG_M128_1).wrapping_add(0xffff);
             r
         };
 
+        let tables = [
+            PowerTable::new(LCG_M128_1),
+            PowerTable::new(LCG_M128_2),
+            PowerTable::new(LCG_M128_3),
+        ];
+
         for _ in 0..1 << 12 {
-            let m = match rnd() % 3 {
-                0 => LCG_M128_1,
-                1 => LCG_M128_2,
-                _ => LCG_M128_3,
-            };
+            let table = &tables[(rnd() % 3) as usize];
+            let m = table.multiplier();
             let p = rnd() | 1;
             let origin = rnd();
 
+            assert_eq!(1, get_inverse_128(p).wrapping_mul(p));
+            assert_eq!(get_inverse_128(p), get_inverse(p));
+            assert_eq!(1, get_inverse(p as u64).wrapping_mul(p as u64));
+
+            // Test affine map algebra.
+            let f = Affine::new(m, p);
+            let g = Affine::new(rnd() | 1, rnd());
+            let h = Affine::from((rnd(), rnd()));
+            let x = rnd();
+            assert_eq!(f.apply(g.apply(x)), (f * g).apply(x));
+            assert_eq!((f * g) * h, f.compose(&g.compose(&h)));
+            assert_eq!(f * Affine::identity(), f);
+            assert_eq!(g * g.inverse(), Affine::identity());
+            assert_eq!(g.inverse() * g, Affine::identity());
+            assert_eq!(g.pow(3), g * g * g);
+            assert_eq!(g.pow(0), Affine::identity());
+            assert_eq!(f.pow(x).apply(origin), get_state(m, p, origin, x));
+            assert_eq!(f.pow(x), Affine::from(get_jump(m, p, x)));
+
             assert_eq!(
                 origin.wrapping_mul(m).wrapping_add(p),
                 get_state(m, p, origin, 1)
@@ -145,6 +520,7 @@ mod tests {
             let state = rnd();
             let n = get_iterations(m, p, origin, state);
             assert_eq!(state, get_state(m, p, origin, n));
+            assert_eq!(state, get_state_128(m, p, origin, n));
 
             let (m_total, p_total) = get_jump(m, p, n);
             assert_eq!(origin.wrapping_mul(m_total).wrapping_add(p_total), state);
@@ -157,6 +533,45 @@ mod tests {
             let h = n & rnd();
             let state_h = get_state(m, p, origin, h);
             assert_eq!(n - h, get_iterations(m, p, state_h, state));
+
+            // Table driven functions agree with the rest.
+            assert_eq!(state, table.get_state(p, origin, n));
+            assert_eq!(n, table.get_iterations(p, origin, state));
+            assert_eq!(get_jump(m, p, n), table.get_jump(p, n));
+            assert_eq!(n - h, table.get_iterations(p, state_h, state));
+
+            let mut lcg = Lcg::new(origin, m, p);
+            lcg.jump(h);
+            assert_eq!(state_h, lcg.state());
+            assert_eq!(h, lcg.position_from(origin));
+            let mut lcg2 = lcg.clone();
+            lcg2.jump(n - h);
+            assert_eq!(state, lcg2.state());
+            assert_eq!(n - h, lcg.distance(&lcg2));
+            assert_eq!(h.wrapping_sub(n), lcg2.distance(&lcg));
+            lcg2.jump(h.wrapping_sub(n));
+            assert_eq!(lcg, lcg2);
+            let mut lcg3 = lcg.clone();
+            lcg3.transform(&lcg.affine().pow(n - h));
+            assert_eq!(state, lcg3.state());
+            lcg3.transform(&lcg.affine().pow(n - h).inverse());
+            assert_eq!(lcg, lcg3);
+            let x = lcg.step();
+            assert_eq!(x, lcg.state());
+            assert_eq!((x >> 64) as u64, lcg2.clone().next_u64());
+            assert_eq!((x >> 96) as u32, lcg2.next_u32());
         }
     }
+
+    #[test]
+    #[should_panic]
+    pub fn panic_on_even_inverse() {
+        Affine::new(6u64, 1).inverse();
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn panic_on_even_inverse_128() {
+        get_inverse_128(6);
+    }
 }
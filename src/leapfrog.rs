@@ -0,0 +1,123 @@
+use super::{Error, RandomAccess, RngCore};
+use core::fmt::Debug;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Leapfrogging splits a stream into interleaved substreams:
+// lane i of k takes every kth output starting from the ith.
+// A jump of k steps is an affine map of LCG state, which we precompute
+// once so that each output takes a single step.
+
+/// Random access RNG that can advance by a precomputed stride in constant time.
+/// Implemented by Krull64 and Krull65.
+pub trait Leap: RandomAccess {
+    /// Precomputed jump of a fixed number of steps in the current stream.
+    type Stride: Clone + Eq + Debug;
+
+    /// Returns the stride of the given number of steps in the current stream.
+    fn stride(&self, steps: u128) -> Self::Stride;
+
+    /// Advances by the stride and returns the 64-bit output there.
+    fn leap(&mut self, stride: &Self::Stride) -> u64;
+}
+
+/// Leapfrog substream: one lane out of a number of interleaved lanes.
+/// If the lanes are merged round-robin, they reproduce the serial stream exactly.
+/// Each output takes constant time.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Leapfrog<R: Leap> {
+    /// Generator at the position of the latest output of this lane.
+    rng: R,
+    /// Jump to the next output of this lane.
+    stride: R::Stride,
+}
+
+impl<R: Leap> Leapfrog<R> {
+    /// Creates lane `lane` of `lanes` interleaved lanes, starting from the current position of `rng`.
+    /// The lane yields the outputs of calls number lane + 1, lane + 1 + lanes,
+    /// lane + 1 + 2 * lanes, ... of `step()` on `rng`. The generator itself is not affected.
+    pub fn new(rng: &R, lanes: u128, lane: u128) -> Self {
+        assert!(lane < lanes, "Leapfrog::new: lane out of range");
+        let mut rng = rng.clone();
+        rng.jump((lane + 1).wrapping_sub(lanes) as i128);
+        let stride = rng.stride(lanes);
+        Leapfrog { rng, stride }
+    }
+
+    /// Returns the underlying generator at the position of the latest output.
+    #[inline]
+    pub fn inner(&self) -> &R {
+        &self.rng
+    }
+
+    /// Generates the next 64-bit random number in the lane.
+    #[inline]
+    pub fn step(&mut self) -> u64 {
+        self.rng.leap(&self.stride)
+    }
+}
+
+impl<R: Leap> RngCore for Leapfrog<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.step() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.step()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let mut chunks = dest.chunks_exact_mut(8);
+        // Always use Little-Endian.
+        for chunk in &mut chunks {
+            chunk.copy_from_slice(&self.step().to_le_bytes());
+        }
+        let rest = chunks.into_remainder();
+        if !rest.is_empty() {
+            let x = self.step();
+            rest.copy_from_slice(&x.to_le_bytes()[0..rest.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    fn test_leapfrog<R: Leap + RngCore>(mut rng: R) {
+        rng.set_position(u128::MAX - 10);
+        for lanes in 1..8 {
+            let mut workers = [(); 8].map(|_| None);
+            for (lane, worker) in workers[0..lanes].iter_mut().enumerate() {
+                *worker = Some(Leapfrog::new(&rng, lanes as u128, lane as u128));
+            }
+            let mut serial = rng.clone();
+            for i in 0..0x40 {
+                let worker = workers[i % lanes].as_mut().unwrap();
+                assert_eq!(serial.next_u64(), worker.next_u64());
+                assert_eq!(serial.position(), worker.inner().position());
+            }
+        }
+    }
+
+    #[test]
+    pub fn run_tests() {
+        test_leapfrog(Krull64::from_64(1));
+        test_leapfrog(Krull65::from_128(1 << 70));
+        let krull = Krull64::new();
+        let mut lane = krull.leapfrog(1 << 100, 3);
+        assert_eq!(Krull64::output_at(0, 4), lane.next_u64());
+        assert_eq!(Krull64::output_at(0, 4 + (1 << 100)), lane.next_u64());
+        let krull = Krull65::new();
+        let mut lane = krull.leapfrog(1 << 100, 3);
+        assert_eq!(Krull65::output_at(0, 4), lane.next_u64());
+        assert_eq!(Krull65::output_at(0, 4 + (1 << 100)), lane.next_u64());
+    }
+}
@@ -1,12 +1,52 @@
 #![no_std]
 
+pub mod access;
+#[cfg(target_has_atomic = "64")]
+pub mod atomic;
+pub mod block;
+pub mod discrete;
+pub mod dist;
+pub mod float;
+pub mod key;
 pub mod krull64;
+pub mod krull64xn;
 pub mod krull65;
+pub mod lattice;
 pub mod lcg;
+pub mod leapfrog;
+mod math;
+pub mod noise;
+pub mod outputs;
+pub mod permutation;
+pub mod sampling;
+pub mod seq;
+pub mod tracked;
+pub mod uniform;
+pub mod weighted;
+mod ziggurat;
 
+pub use access::*;
+#[cfg(target_has_atomic = "64")]
+pub use atomic::*;
+pub use block::*;
+pub use discrete::*;
+pub use dist::*;
+pub use float::*;
+pub use key::*;
 pub use krull64::*;
+pub use krull64xn::*;
 pub use krull65::*;
+pub use lattice::*;
+pub use leapfrog::*;
+pub use noise::*;
+pub use outputs::*;
+pub use permutation::*;
 pub use rand_core::*;
+pub use sampling::*;
+pub use seq::*;
+pub use tracked::*;
+pub use uniform::*;
+pub use weighted::*;
 
 // LCG multipliers from Steele, G. and Vigna, S.,
 // Computationally Easy, Spectrally Good Multipliers for
@@ -24,6 +64,11 @@ pub const LCG_M65_2: u128 = 0x1d605bbb58c8abbfd;
 pub const LCG_M65_3: u128 = 0x1d7d8dd3a6a72b43d;
 pub const LCG_M65_4: u128 = 0x1f20529e418340d05;
 
+// Power tables of the 65-bit LCG multipliers in use, for fast random access.
+// Each takes 4 KiB. Reading them in const fns requires Rust 1.83.
+pub static LCG_M65_1_POWERS: lcg::PowerTable = lcg::PowerTable::new(LCG_M65_1);
+pub static LCG_M65_4_POWERS: lcg::PowerTable = lcg::PowerTable::new(LCG_M65_4);
+
 // 64-bit LCG multipliers.
 pub const LCG_M64_1: u64 = 0xd1342543de82ef95;
 pub const LCG_M64_2: u64 = 0xaf251af3b0f025b5;
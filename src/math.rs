@@ -0,0 +1,313 @@
+use core::f64::consts::{LN_2, LOG2_E};
+
+// Elementary functions for distributions. The core library has no floating point math,
+// so we implement the few functions we need with basic IEEE 754 arithmetic only.
+// Results are therefore identical on all platforms.
+// The exp and ln implementations follow FreeBSD msun (as found in musl),
+// Copyright (C) 1993 by Sun Microsystems, Inc. Permission to use, copy, modify,
+// and distribute this software is freely granted, provided that this notice is preserved.
+
+const LN2_HI: f64 = 0.6931471803691238;
+const LN2_LO: f64 = 1.9082149292705877e-10;
+
+/// Returns 2**k for k in -1022..=1023.
+#[inline]
+fn pow2(k: i32) -> f64 {
+    f64::from_bits(((0x3ff + k) as u64) << 52)
+}
+
+/// Returns x * 2**k.
+fn scale(x: f64, k: i32) -> f64 {
+    if k > 1023 {
+        x * pow2(1023) * pow2(k - 1023)
+    } else if k < -1022 {
+        // Scale in two steps. The result is rounded only once,
+        // in the second step into the subnormal range.
+        x * pow2(-969) * pow2(k + 969)
+    } else {
+        x * pow2(k)
+    }
+}
+
+/// Exponential function. Accurate to within 1 ulp.
+pub(crate) fn exp(x: f64) -> f64 {
+    const P1: f64 = 0.16666666666666602;
+    const P2: f64 = -0.0027777777777015593;
+    const P3: f64 = 6.613756321437934e-05;
+    const P4: f64 = -1.6533902205465252e-06;
+    const P5: f64 = 4.1381367970572385e-08;
+    if x.is_nan() {
+        return x;
+    }
+    if x > 709.782712893384 {
+        return f64::INFINITY;
+    }
+    if x < -745.1332191019411 {
+        return 0.0;
+    }
+    // Reduce x = k * ln 2 + r with |r| <= ln(2) / 2.
+    let k = (LOG2_E * x + if x < 0.0 { -0.5 } else { 0.5 }) as i32;
+    let hi = x - k as f64 * LN2_HI;
+    let lo = k as f64 * LN2_LO;
+    let r = hi - lo;
+    // exp(r) = 1 + r + r * c / (2 - c), where c approximates r - r**2 / 6 + ...
+    let rr = r * r;
+    let c = r - rr * (P1 + rr * (P2 + rr * (P3 + rr * (P4 + rr * P5))));
+    let y = 1.0 + (r * c / (2.0 - c) - lo + hi);
+    scale(y, k)
+}
+
+/// Natural logarithm. Accurate to within 1 ulp. Returns NaN for negative x
+/// and negative infinity for zero.
+pub(crate) fn ln(x: f64) -> f64 {
+    const LG1: f64 = 0.6666666666666735;
+    const LG2: f64 = 0.3999999999940942;
+    const LG3: f64 = 0.2857142874366239;
+    const LG4: f64 = 0.22222198432149784;
+    const LG5: f64 = 0.1818357216161805;
+    const LG6: f64 = 0.15313837699209373;
+    const LG7: f64 = 0.14798198605116586;
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if x == f64::INFINITY {
+        return x;
+    }
+    let mut bits = x.to_bits();
+    let mut k = 0;
+    if bits < 1 << 52 {
+        // Normalize subnormals.
+        bits = (x * pow2(54)).to_bits();
+        k -= 54;
+    }
+    // Reduce x = 2**k * (1 + f) with sqrt(2) / 2 < 1 + f < sqrt(2).
+    bits += 0x3ff0_0000_0000_0000 - 0x3fe6_a09e_0000_0000;
+    k += (bits >> 52) as i32 - 0x3ff;
+    bits = (bits & 0x000f_ffff_ffff_ffff) + 0x3fe6_a09e_0000_0000;
+    let f = f64::from_bits(bits) - 1.0;
+    // ln(1 + f) = 2s + 2s**3 / 3 + ..., where s = f / (2 + f).
+    let hfsq = 0.5 * f * f;
+    let s = f / (2.0 + f);
+    let z = s * s;
+    let w = z * z;
+    let t1 = w * (LG2 + w * (LG4 + w * LG6));
+    let t2 = z * (LG1 + w * (LG3 + w * (LG5 + w * LG7)));
+    let r = t2 + t1;
+    let dk = k as f64;
+    s * (hfsq + r) + dk * LN2_LO - hfsq + f + dk * LN2_HI
+}
+
+/// Square root. Accurate to within 1 ulp. Returns NaN for negative x.
+pub(crate) fn sqrt(x: f64) -> f64 {
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 || x == f64::INFINITY {
+        return x;
+    }
+    // Split x = m * 4**k with m in [1, 4) so that sqrt(x) = sqrt(m) * 2**k.
+    let bits = x.to_bits();
+    let (m, k) = if bits < 1 << 52 {
+        let y = x * pow2(54);
+        let e = (y.to_bits() >> 52) as i32 - 0x3ff;
+        (scale(y, -(e & !1)), (e >> 1) - 27)
+    } else {
+        let e = (bits >> 52) as i32 - 0x3ff;
+        (scale(x, -(e & !1)), e >> 1)
+    };
+    // Newton iterations converge quadratically from a linear initial guess.
+    let mut y = 0.5 + 0.5 * m;
+    for _ in 0..5 {
+        y = 0.5 * (y + m / y);
+    }
+    scale(y, k)
+}
+
+/// Rounds x down to an integer.
+pub(crate) fn floor(x: f64) -> f64 {
+    // Floats of magnitude 2**52 and above are integers already. This covers infinities and NaN too.
+    if x.is_nan() || x.abs() >= 4503599627370496.0 {
+        return x;
+    }
+    // Conversion truncates towards zero, so we have to adjust negative values.
+    let i = x as i64 as f64;
+    if i > x {
+        i - 1.0
+    } else {
+        i
+    }
+}
+
+/// Returns ln(1 + x), accurately also for x close to zero.
+pub(crate) fn ln_1p(x: f64) -> f64 {
+    // Goldberg, D., What Every Computer Scientist Should Know About Floating-Point Arithmetic (1991).
+    let u = 1.0 + x;
+    if u == 1.0 {
+        x
+    } else {
+        ln(u) * x / (u - 1.0)
+    }
+}
+
+/// Returns exp(x) - 1, accurately also for x close to zero.
+pub(crate) fn exp_m1(x: f64) -> f64 {
+    // Kahan's counterpart of the trick in ln_1p.
+    let u = exp(x);
+    if u == 1.0 {
+        x
+    } else if u - 1.0 == -1.0 || u == f64::INFINITY {
+        u - 1.0
+    } else {
+        (u - 1.0) * x / ln(u)
+    }
+}
+
+const HALF_LN_2PI: f64 = 0.9189385332046728;
+
+/// Returns ln(k!). Accurate to within a few ulps.
+pub(crate) fn ln_factorial(k: u64) -> f64 {
+    const TABLE: [f64; 16] = [
+        0.0,
+        0.0,
+        LN_2,
+        1.791759469228055,
+        3.1780538303479458,
+        4.787491742782046,
+        6.579251212010101,
+        8.525161361065415,
+        10.60460290274525,
+        12.801827480081469,
+        15.104412573075516,
+        17.502307845873887,
+        19.987214495661885,
+        22.552163853123425,
+        25.19122118273868,
+        27.89927138384089,
+    ];
+    if k < 16 {
+        TABLE[k as usize]
+    } else {
+        let n = k as f64;
+        (n + 0.5) * ln(n) - n + HALF_LN_2PI + stirling_error(k)
+    }
+}
+
+/// Returns the error of the Stirling approximation, ln(k!) - (k + 1/2) ln(k) + k - ln(2 pi) / 2,
+/// for positive k. The absolute error of the result is below 1.0e-13.
+pub(crate) fn stirling_error(k: u64) -> f64 {
+    let n = k as f64;
+    if k < 16 {
+        ln_factorial(k) - ((n + 0.5) * ln(n) - n + HALF_LN_2PI)
+    } else {
+        let r = 1.0 / n;
+        let rr = r * r;
+        r * (1.0 / 12.0
+            - rr * (1.0 / 360.0 - rr * (1.0 / 1260.0 - rr * (1.0 / 1680.0 - rr * (1.0 / 1188.0)))))
+    }
+}
+
+/// Returns the deviance term x ln(x / m) + m - x for non-negative x and positive m,
+/// accurately also when x is close to m.
+pub(crate) fn bd0(x: f64, m: f64) -> f64 {
+    // Loader, C., Fast and Accurate Computation of Binomial Probabilities (2000).
+    if (x - m).abs() < 0.1 * (x + m) {
+        // Series in v = (x - m) / (x + m), which is less than 0.1 in magnitude.
+        let v = (x - m) / (x + m);
+        let vv = v * v;
+        let mut s = (x - m) * v;
+        let mut term = 2.0 * x * v;
+        for j in 1..100 {
+            term *= vv;
+            let next = s + term / (2 * j + 1) as f64;
+            if next == s {
+                break;
+            }
+            s = next;
+        }
+        s
+    } else if x == 0.0 {
+        m
+    } else {
+        x * ln(x / m) + m - x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::{E, SQRT_2};
+
+    /// Checks that x and y differ by at most 1 ulp.
+    fn assert_close(x: f64, y: f64) {
+        assert!(x.to_bits().abs_diff(y.to_bits()) <= 1, "{} != {}", x, y);
+    }
+
+    #[test]
+    pub fn run_tests() {
+        assert_eq!(1.0, exp(0.0));
+        assert_close(E, exp(1.0));
+        assert_close(1.0 / E, exp(-1.0));
+        assert!((exp(10.0 * core::f64::consts::LN_10) / 1.0e10 - 1.0).abs() < 1.0e-14);
+        assert_eq!(f64::INFINITY, exp(710.0));
+        assert_eq!(0.0, exp(-746.0));
+        assert!(exp(-740.0) > 0.0);
+        assert!(exp(f64::NAN).is_nan());
+
+        assert_eq!(0.0, ln(1.0));
+        assert_close(1.0, ln(E));
+        assert_close(LN_2, ln(2.0));
+        assert_close(-LN_2, ln(0.5));
+        assert_close(-1074.0 * LN_2, ln(f64::from_bits(1)));
+        assert_eq!(f64::NEG_INFINITY, ln(0.0));
+        assert!(ln(-1.0).is_nan());
+
+        assert_eq!(2.0, sqrt(4.0));
+        assert_eq!(0.5, sqrt(0.25));
+        assert_close(SQRT_2, sqrt(2.0));
+        assert_eq!(f64::from_bits((0x3ff - 537) << 52), sqrt(f64::from_bits(1)));
+        assert_close(1.0e150, sqrt(1.0e300));
+        assert!(sqrt(-1.0).is_nan());
+
+        assert_eq!(-3.0, floor(-2.5));
+        assert_eq!(-2.0, floor(-2.0));
+        assert_eq!(2.0, floor(2.5));
+        assert_eq!(1.0e-20, ln_1p(1.0e-20));
+        assert_close(-1.0e-10 - 0.5e-20, ln_1p(-1.0e-10));
+        assert_close(LN_2, ln_1p(1.0));
+        assert_eq!(1.0e-20, exp_m1(1.0e-20));
+        assert_close(1.0e-10 + 0.5e-20, exp_m1(1.0e-10));
+        assert!((exp_m1(1.0) - (E - 1.0)).abs() < 1.0e-15);
+        assert_eq!(-1.0, exp_m1(-800.0));
+        assert_eq!(f64::INFINITY, exp_m1(800.0));
+
+        // ln(20!) and ln(1000!).
+        assert!((ln_factorial(20) / 42.335616460753485 - 1.0).abs() < 1.0e-15);
+        assert!((ln_factorial(1000) / 5912.128178488163 - 1.0).abs() < 1.0e-15);
+        assert!((stirling_error(16) - 0.0052076559196096404).abs() < 1.0e-14);
+        assert!((stirling_error(15) - 0.005554733551962801).abs() < 1.0e-14);
+        assert!((stirling_error(1) - 0.08106146679532726).abs() < 1.0e-14);
+        assert_eq!(0.0, bd0(5.0, 5.0));
+        assert_close(0.004841197784757355, bd0(1.1, 1.0));
+        assert_close(4.547472063262434e-13, bd0(1.0 + 1.0 / 1048576.0, 1.0));
+        assert!((bd0(2.0, 1.0) - (2.0 * LN_2 - 1.0)).abs() < 1.0e-15);
+        assert_eq!(1.0, bd0(0.0, 1.0));
+        assert_eq!(f64::INFINITY, floor(f64::INFINITY));
+        assert_eq!(1.0e20, floor(1.0e20));
+        let mut sum = 0.0;
+        for k in 1..100 {
+            sum += ln(k as f64);
+            assert!((ln_factorial(k) - sum).abs() <= 1.0e-13 * sum);
+        }
+
+        let mut x = 1.0e-300;
+        while x < 1.0e300 {
+            assert!((exp(ln(x)) / x - 1.0).abs() < 1.0e-12);
+            let y = sqrt(x);
+            assert!((y * y - x).abs() <= 3.0 * x * f64::EPSILON);
+            x *= 1.7;
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use super::krull64::hash;
+use super::{Krull64, Krull65};
+use core::f64::consts::{FRAC_1_SQRT_2, SQRT_2};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Noise fields are built on a hash of integer lattice cells keyed by a stream.
+// The hash is a linear combination of cell coordinates fed through the Krull64
+// output hash, which is much faster than random access to a stream.
+// Only basic IEEE 754 arithmetic and integer conversions are used,
+// so results are identical on all platforms.
+
+/// Odd multipliers for combining cell coordinates.
+const CELL_X: u64 = 0x9e3779b97f4a7c15;
+const CELL_Y: u64 = 0xc2b2ae3d27d4eb4f;
+const CELL_Z: u64 = 0x165667b19e3779f9;
+
+/// Keys that make noise fields of different dimensions independent.
+const DIMENSION_KEYS: [u64; 3] = [0x27d4eb2f165667c5, 0x85ebca77c2b2ae63, 0xff51afd7ed558ccd];
+
+/// Key XORed into 128-bit streams.
+const STREAM_KEY: u64 = 0xc4ceb9fe1a85ec53;
+
+/// Gradients of 2-dimensional gradient noise: 8 unit vectors at equal angles.
+const GRADIENTS_2: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    (-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    (FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+    (-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+];
+
+/// Gradients of 3-dimensional gradient noise: the 12 edges of a cube
+/// padded to 16 entries as in Perlin's improved noise.
+const GRADIENTS_3: [(f64, f64, f64); 16] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+    (1.0, 1.0, 0.0),
+    (0.0, -1.0, 1.0),
+    (-1.0, 1.0, 0.0),
+    (0.0, -1.0, -1.0),
+];
+
+/// Splits x into the integer cell containing it and the offset inside the cell in [0, 1).
+/// Coordinates are assumed to be finite and less than 2**63 in magnitude.
+#[inline]
+fn split(x: f64) -> (i64, f64) {
+    // Conversion truncates towards zero, so we have to adjust negative values.
+    let i = x as i64;
+    let i = if (i as f64) > x { i - 1 } else { i };
+    (i, x - i as f64)
+}
+
+/// Quintic fade curve 6t**5 - 15t**4 + 10t**3, which has zero first
+/// and second derivatives at the ends of the unit interval.
+#[inline]
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Linear interpolation from a to b.
+#[inline]
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Converts a hash into a value in [-1, 1).
+#[inline]
+fn to_signed_unit(h: u64) -> f64 {
+    (h >> 11) as f64 * (1.0 / (1u64 << 52) as f64) - 1.0
+}
+
+/// Noise field keyed by a stream. Provides value noise, which interpolates
+/// random values at integer lattice points, and gradient noise (Perlin noise),
+/// which interpolates random gradients and is zero at integer lattice points.
+/// Both have a feature size of 1 and are continuous with continuous first and
+/// second derivatives. Value noise lies in [-1, 1] and gradient noise approximately so.
+/// Noise fields keyed by different streams are independent.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Noise {
+    /// Cell hash key.
+    key: u64,
+}
+
+impl Noise {
+    /// Creates a noise field keyed by a Krull64 stream.
+    /// Distinct streams result in distinct noise fields.
+    pub const fn from_64(stream: u64) -> Self {
+        Noise {
+            key: hash(stream ^ STREAM_KEY),
+        }
+    }
+
+    /// Creates a noise field keyed by a Krull65 stream.
+    pub const fn from_128(stream: u128) -> Self {
+        Noise {
+            key: hash(stream as u64 ^ hash((stream >> 64) as u64 ^ STREAM_KEY)),
+        }
+    }
+
+    /// Hashes a 1-dimensional cell.
+    #[inline]
+    fn cell_1d(&self, x: i64) -> u64 {
+        hash((self.key ^ DIMENSION_KEYS[0]).wrapping_add((x as u64).wrapping_mul(CELL_X)))
+    }
+
+    /// Hashes a 2-dimensional cell.
+    #[inline]
+    fn cell_2d(&self, x: i64, y: i64) -> u64 {
+        hash(
+            (self.key ^ DIMENSION_KEYS[1])
+                .wrapping_add((x as u64).wrapping_mul(CELL_X))
+                .wrapping_add((y as u64).wrapping_mul(CELL_Y)),
+        )
+    }
+
+    /// Hashes a 3-dimensional cell.
+    #[inline]
+    fn cell_3d(&self, x: i64, y: i64, z: i64) -> u64 {
+        hash(
+            (self.key ^ DIMENSION_KEYS[2])
+                .wrapping_add((x as u64).wrapping_mul(CELL_X))
+                .wrapping_add((y as u64).wrapping_mul(CELL_Y))
+                .wrapping_add((z as u64).wrapping_mul(CELL_Z)),
+        )
+    }
+
+    /// Returns 1-dimensional value noise at x, in [-1, 1].
+    pub fn value_1d(&self, x: f64) -> f64 {
+        let (ix, fx) = split(x);
+        let v0 = to_signed_unit(self.cell_1d(ix));
+        let v1 = to_signed_unit(self.cell_1d(ix.wrapping_add(1)));
+        lerp(v0, v1, fade(fx))
+    }
+
+    /// Returns 2-dimensional value noise at (x, y), in [-1, 1].
+    pub fn value_2d(&self, x: f64, y: f64) -> f64 {
+        let (ix, fx) = split(x);
+        let (iy, fy) = split(y);
+        let (jx, jy) = (ix.wrapping_add(1), iy.wrapping_add(1));
+        let u = fade(fx);
+        let v00 = to_signed_unit(self.cell_2d(ix, iy));
+        let v10 = to_signed_unit(self.cell_2d(jx, iy));
+        let v01 = to_signed_unit(self.cell_2d(ix, jy));
+        let v11 = to_signed_unit(self.cell_2d(jx, jy));
+        lerp(lerp(v00, v10, u), lerp(v01, v11, u), fade(fy))
+    }
+
+    /// Returns 3-dimensional value noise at (x, y, z), in [-1, 1].
+    pub fn value_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (ix, fx) = split(x);
+        let (iy, fy) = split(y);
+        let (iz, fz) = split(z);
+        let (jx, jy, jz) = (ix.wrapping_add(1), iy.wrapping_add(1), iz.wrapping_add(1));
+        let u = fade(fx);
+        let v = fade(fy);
+        let v000 = to_signed_unit(self.cell_3d(ix, iy, iz));
+        let v100 = to_signed_unit(self.cell_3d(jx, iy, iz));
+        let v010 = to_signed_unit(self.cell_3d(ix, jy, iz));
+        let v110 = to_signed_unit(self.cell_3d(jx, jy, iz));
+        let v001 = to_signed_unit(self.cell_3d(ix, iy, jz));
+        let v101 = to_signed_unit(self.cell_3d(jx, iy, jz));
+        let v011 = to_signed_unit(self.cell_3d(ix, jy, jz));
+        let v111 = to_signed_unit(self.cell_3d(jx, jy, jz));
+        lerp(
+            lerp(lerp(v000, v100, u), lerp(v010, v110, u), v),
+            lerp(lerp(v001, v101, u), lerp(v011, v111, u), v),
+            fade(fz),
+        )
+    }
+
+    /// Returns 1-dimensional gradient noise at x, approximately in [-1, 1].
+    pub fn gradient_1d(&self, x: f64) -> f64 {
+        let (ix, fx) = split(x);
+        let g0 = to_signed_unit(self.cell_1d(ix));
+        let g1 = to_signed_unit(self.cell_1d(ix.wrapping_add(1)));
+        // The maximum magnitude of the unscaled noise is 1/2.
+        2.0 * lerp(g0 * fx, g1 * (fx - 1.0), fade(fx))
+    }
+
+    /// Dot product of the gradient of a 2-dimensional cell with the offset (dx, dy).
+    #[inline]
+    fn dot_2d(&self, x: i64, y: i64, dx: f64, dy: f64) -> f64 {
+        let (gx, gy) = GRADIENTS_2[(self.cell_2d(x, y) >> 61) as usize];
+        gx * dx + gy * dy
+    }
+
+    /// Returns 2-dimensional gradient noise at (x, y), approximately in [-1, 1].
+    pub fn gradient_2d(&self, x: f64, y: f64) -> f64 {
+        let (ix, fx) = split(x);
+        let (iy, fy) = split(y);
+        let (jx, jy) = (ix.wrapping_add(1), iy.wrapping_add(1));
+        let u = fade(fx);
+        let n00 = self.dot_2d(ix, iy, fx, fy);
+        let n10 = self.dot_2d(jx, iy, fx - 1.0, fy);
+        let n01 = self.dot_2d(ix, jy, fx, fy - 1.0);
+        let n11 = self.dot_2d(jx, jy, fx - 1.0, fy - 1.0);
+        // The maximum magnitude of the unscaled noise is 1/sqrt(2).
+        SQRT_2 * lerp(lerp(n00, n10, u), lerp(n01, n11, u), fade(fy))
+    }
+
+    /// Dot product of the gradient of a 3-dimensional cell with the offset (dx, dy, dz).
+    #[inline]
+    fn dot_3d(&self, x: i64, y: i64, z: i64, dx: f64, dy: f64, dz: f64) -> f64 {
+        let (gx, gy, gz) = GRADIENTS_3[(self.cell_3d(x, y, z) >> 60) as usize];
+        gx * dx + gy * dy + gz * dz
+    }
+
+    /// Returns 3-dimensional gradient noise at (x, y, z), approximately in [-1, 1].
+    pub fn gradient_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (ix, fx) = split(x);
+        let (iy, fy) = split(y);
+        let (iz, fz) = split(z);
+        let (jx, jy, jz) = (ix.wrapping_add(1), iy.wrapping_add(1), iz.wrapping_add(1));
+        let (gx, gy, gz) = (fx - 1.0, fy - 1.0, fz - 1.0);
+        let u = fade(fx);
+        let v = fade(fy);
+        let n000 = self.dot_3d(ix, iy, iz, fx, fy, fz);
+        let n100 = self.dot_3d(jx, iy, iz, gx, fy, fz);
+        let n010 = self.dot_3d(ix, jy, iz, fx, gy, fz);
+        let n110 = self.dot_3d(jx, jy, iz, gx, gy, fz);
+        let n001 = self.dot_3d(ix, iy, jz, fx, fy, gz);
+        let n101 = self.dot_3d(jx, iy, jz, gx, fy, gz);
+        let n011 = self.dot_3d(ix, jy, jz, fx, gy, gz);
+        let n111 = self.dot_3d(jx, jy, jz, gx, gy, gz);
+        lerp(
+            lerp(lerp(n000, n100, u), lerp(n010, n110, u), v),
+            lerp(lerp(n001, n101, u), lerp(n011, n111, u), v),
+            fade(fz),
+        )
+    }
+
+    /// Returns 1-dimensional value noise at x, in [-1, 1].
+    /// Computed in double precision.
+    pub fn value_1d_f32(&self, x: f32) -> f32 {
+        self.value_1d(x as f64) as f32
+    }
+
+    /// Returns 2-dimensional value noise at (x, y), in [-1, 1].
+    /// Computed in double precision.
+    pub fn value_2d_f32(&self, x: f32, y: f32) -> f32 {
+        self.value_2d(x as f64, y as f64) as f32
+    }
+
+    /// Returns 3-dimensional value noise at (x, y, z), in [-1, 1].
+    /// Computed in double precision.
+    pub fn value_3d_f32(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.value_3d(x as f64, y as f64, z as f64) as f32
+    }
+
+    /// Returns 1-dimensional gradient noise at x, approximately in [-1, 1].
+    /// Computed in double precision.
+    pub fn gradient_1d_f32(&self, x: f32) -> f32 {
+        self.gradient_1d(x as f64) as f32
+    }
+
+    /// Returns 2-dimensional gradient noise at (x, y), approximately in [-1, 1].
+    /// Computed in double precision.
+    pub fn gradient_2d_f32(&self, x: f32, y: f32) -> f32 {
+        self.gradient_2d(x as f64, y as f64) as f32
+    }
+
+    /// Returns 3-dimensional gradient noise at (x, y, z), approximately in [-1, 1].
+    /// Computed in double precision.
+    pub fn gradient_3d_f32(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.gradient_3d(x as f64, y as f64, z as f64) as f32
+    }
+}
+
+impl From<&Krull64> for Noise {
+    /// Creates a noise field keyed by the stream of the generator.
+    fn from(krull: &Krull64) -> Self {
+        Noise::from_64(krull.stream())
+    }
+}
+
+impl From<&Krull65> for Noise {
+    /// Creates a noise field keyed by the stream of the generator.
+    fn from(krull: &Krull65) -> Self {
+        Noise::from_128(krull.stream())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    #[test]
+    pub fn run_tests() {
+        let mut r: u128 = 0;
+        let mut rnd = || -> f64 {
+            r = r.wrapping_mul(LCG_M128_1).wrapping_add(0xffff);
+            ((r >> 64) as i64) as f64 * (1.0 / (1u64 << 56) as f64)
+        };
+
+        assert_eq!((-1, 0.5), split(-0.5));
+        assert_eq!((-2, 0.0), split(-2.0));
+        assert_eq!((3, 0.25), split(3.25));
+
+        let noise = Noise::from(&Krull64::from_64(1));
+        assert_eq!(Noise::from_64(1), noise);
+        assert_ne!(Noise::from_64(2), noise);
+        assert_ne!(Noise::from_128(1), noise);
+        assert_eq!(
+            Noise::from_128(1 << 64),
+            Noise::from(&Krull65::from_128(1 << 64))
+        );
+
+        // Known answers guard against accidental changes to noise fields.
+        assert_eq!(0.21276764430138628, noise.value_1d(0.5));
+        assert_eq!(0.1489057337802377, noise.value_2d(0.5, 1.5));
+        assert_eq!(-0.03535713555419065, noise.value_3d(0.5, 1.5, -2.5));
+        assert_eq!(0.3219814901400017, noise.gradient_1d(0.25));
+        assert_eq!(0.26607909092219784, noise.gradient_2d(0.25, 1.5));
+        assert_eq!(0.2823486328125, noise.gradient_3d(0.25, 1.5, -2.5));
+
+        let mut sum_value = 0.0;
+        let mut sum_gradient = 0.0;
+        let n = 1 << 12;
+        for _ in 0..n {
+            let (x, y, z) = (rnd(), rnd(), rnd());
+            for v in [
+                noise.value_1d(x),
+                noise.value_2d(x, y),
+                noise.value_3d(x, y, z),
+            ] {
+                assert!((-1.0..=1.0).contains(&v));
+                sum_value += v;
+            }
+            for v in [
+                noise.gradient_1d(x),
+                noise.gradient_2d(x, y),
+                noise.gradient_3d(x, y, z),
+            ] {
+                assert!((-1.1..=1.1).contains(&v));
+                sum_gradient += v;
+            }
+
+            // Gradient noise vanishes at lattice points,
+            // where value noise is equal to the cell value.
+            let (ix, iy, iz) = (split(x).0, split(y).0, split(z).0);
+            let (cx, cy, cz) = (ix as f64, iy as f64, iz as f64);
+            assert_eq!(0.0, noise.gradient_1d(cx));
+            assert_eq!(0.0, noise.gradient_2d(cx, cy));
+            assert_eq!(0.0, noise.gradient_3d(cx, cy, cz));
+            assert_eq!(to_signed_unit(noise.cell_1d(ix)), noise.value_1d(cx));
+            assert_eq!(
+                to_signed_unit(noise.cell_2d(ix, iy)),
+                noise.value_2d(cx, cy)
+            );
+            assert_eq!(
+                to_signed_unit(noise.cell_3d(ix, iy, iz)),
+                noise.value_3d(cx, cy, cz)
+            );
+
+            // Noise is continuous.
+            let d = 1.0e-6;
+            for (v0, v1) in [
+                (noise.value_2d(x, y), noise.value_2d(x + d, y - d)),
+                (noise.value_3d(x, y, z), noise.value_3d(x, y + d, z + d)),
+                (noise.gradient_1d(x), noise.gradient_1d(x - d)),
+                (
+                    noise.gradient_3d(x, y, z),
+                    noise.gradient_3d(x + d, y, z - d),
+                ),
+            ] {
+                assert!((v1 - v0).abs() < 1.0e-4);
+            }
+
+            assert_eq!(
+                noise.gradient_3d(x as f32 as f64, y as f32 as f64, z as f32 as f64) as f32,
+                noise.gradient_3d_f32(x as f32, y as f32, z as f32)
+            );
+        }
+        // Noise is centered on zero.
+        assert!((sum_value / (3 * n) as f64).abs() < 0.05);
+        assert!((sum_gradient / (3 * n) as f64).abs() < 0.05);
+    }
+}
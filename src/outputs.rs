@@ -0,0 +1,181 @@
+use super::RandomAccess;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+/// Iterator over the outputs at a range of positions in a stream.
+/// Iterates from both ends in constant time per item
+/// and skips items with `nth` and `nth_back` in logarithmic time.
+/// Ranges can be longer than `usize`, so the iterator is not an `ExactSizeIterator`:
+/// `len_128` returns the exact length, and `count()` panics if it does not fit.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Outputs<R: RandomAccess> {
+    /// Generator positioned just before the front of the range.
+    front: R,
+    /// Generator positioned at the back (exclusive) of the range.
+    back: R,
+    /// Number of items left.
+    len: u128,
+}
+
+impl<R: RandomAccess> Outputs<R> {
+    /// Creates an iterator over the outputs at the given range of positions
+    /// in the current stream of the generator.
+    /// The generator itself is not affected.
+    pub fn new(rng: &R, positions: Range<u128>) -> Self {
+        let len = positions.end.saturating_sub(positions.start);
+        let mut front = rng.clone();
+        front.set_position(positions.start.wrapping_sub(1));
+        let mut back = rng.clone();
+        back.set_position(positions.start + len);
+        Outputs { front, back, len }
+    }
+
+    /// Returns the number of items left as a `u128`.
+    #[inline]
+    pub fn len_128(&self) -> u128 {
+        self.len
+    }
+
+    /// Returns the range of positions left.
+    pub fn positions(&self) -> Range<u128> {
+        let end = self.back.position();
+        end.wrapping_sub(self.len)..end
+    }
+}
+
+impl<R: RandomAccess> Iterator for Outputs<R> {
+    type Item = u64;
+
+    #[inline]
+    fn next(&mut self) -> Option<u64> {
+        if self.len > 0 {
+            self.len -= 1;
+            Some(self.front.step())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.len) {
+            Ok(len) => (len, Some(len)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<u64> {
+        if (n as u128) < self.len {
+            self.front.jump(n as i128);
+            self.len -= n as u128;
+            self.next()
+        } else {
+            self.front = self.back.clone();
+            self.len = 0;
+            None
+        }
+    }
+
+    fn count(self) -> usize {
+        usize::try_from(self.len).expect("Outputs::count: length overflows usize")
+    }
+
+    fn last(mut self) -> Option<u64> {
+        self.next_back()
+    }
+}
+
+impl<R: RandomAccess> DoubleEndedIterator for Outputs<R> {
+    #[inline]
+    fn next_back(&mut self) -> Option<u64> {
+        if self.len > 0 {
+            self.len -= 1;
+            Some(self.back.step_back())
+        } else {
+            None
+        }
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<u64> {
+        if (n as u128) < self.len {
+            self.back.jump(-(n as i128));
+            self.len -= n as u128;
+            self.next_back()
+        } else {
+            self.back = self.front.clone();
+            self.len = 0;
+            None
+        }
+    }
+}
+
+impl<R: RandomAccess> FusedIterator for Outputs<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    fn test_outputs<R: RandomAccess + RngCore>(mut rng: R) {
+        rng.set_position(0xfff0);
+        let mut expected = [0u64; 0x40];
+        for x in expected.iter_mut() {
+            *x = rng.next_u64();
+        }
+        rng.reset();
+        let start = 0xfff1;
+        let end = start + expected.len() as u128;
+
+        let outputs = Outputs::new(&rng, start..end);
+        assert_eq!(expected.len(), outputs.clone().count());
+        assert_eq!(expected.len() as u128, outputs.len_128());
+        assert_eq!(start..end, outputs.positions());
+        assert!(outputs.clone().eq(expected.iter().copied()));
+        assert!(outputs.clone().rev().eq(expected.iter().rev().copied()));
+        assert_eq!(Some(expected[expected.len() - 1]), outputs.clone().last());
+        assert_eq!(0, rng.position());
+
+        // Consume from both ends and skip.
+        let mut outputs = Outputs::new(&rng, start..end);
+        assert_eq!(Some(expected[0]), outputs.next());
+        assert_eq!(Some(expected[0x3f]), outputs.next_back());
+        assert_eq!(Some(expected[4]), outputs.nth(3));
+        assert_eq!(Some(expected[0x3a]), outputs.nth_back(4));
+        assert_eq!(0x3a - 5, outputs.len_128());
+        assert_eq!(start + 5..start + 0x3a, outputs.positions());
+        assert_eq!(Some(expected[5]), outputs.next());
+        assert_eq!(Some(expected[0x39]), outputs.next_back());
+        assert_eq!(None, outputs.nth(0x33));
+        assert_eq!(0, outputs.len_128());
+        assert_eq!(None, outputs.next());
+        assert_eq!(None, outputs.next_back());
+
+        let mut outputs = Outputs::new(&rng, start..end);
+        assert_eq!(None, outputs.nth_back(0x40));
+        assert_eq!(None, outputs.next());
+
+        // Ranges can be empty or span the whole stream.
+        assert_eq!(0, Outputs::new(&rng, end..start).count());
+        let mut outputs = Outputs::new(&rng, 0..u128::MAX);
+        assert_eq!(u128::MAX, outputs.len_128());
+        assert_eq!((usize::MAX, None), outputs.size_hint());
+        assert_eq!(Some(R::output_at(rng.stream(), 0)), outputs.next());
+        assert_eq!(
+            Some(R::output_at(rng.stream(), u128::MAX - 1)),
+            outputs.next_back()
+        );
+    }
+
+    #[test]
+    pub fn run_tests() {
+        test_outputs(Krull64::from_64(1));
+        test_outputs(Krull65::from_128(1 << 100));
+        assert!(Krull64::new()
+            .outputs(1..0x11)
+            .eq((1..0x11).map(|i| Krull64::output_at(0, i))));
+        assert!(Krull65::new()
+            .outputs(1..0x11)
+            .rev()
+            .eq((1..0x11).rev().map(|i| Krull65::output_at(0, i))));
+    }
+}
@@ -0,0 +1,333 @@
+use super::krull64::hash;
+use super::{Krull64, Krull65};
+use core::iter::FusedIterator;
+use core::ops::Range;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// A permutation of 0..n is computed one element at a time, without memory.
+// A balanced Feistel network, whose round function is the Krull64 output hash,
+// permutes the 2h-bit numbers, where 2**(2h) is the smallest even power of two not less than n.
+// Cycle walking restricts it to 0..n: the network is applied repeatedly until the result
+// is less than n. As 2**(2h) < 4n, this takes fewer than 4 applications on average.
+// Permutations are part of the reproducibility contract,
+// so the network is versioned: the permutations of a version never change.
+
+/// Version of the permutation computed by `Permutation`.
+/// Permutations are identical on all platforms and stable within a version.
+pub const PERMUTATION_VERSION: u32 = 1;
+
+/// Number of Feistel rounds. More rounds than the usual 4 improve mixing in small domains.
+const ROUNDS: usize = 6;
+
+/// Round keys of the Feistel network.
+const ROUND_KEYS: [u64; ROUNDS] = [
+    0x8f2d6fa3a1c1b8f1,
+    0x53a1f3a0d5d9c3a7,
+    0xb1e6c7a3f7e9d415,
+    0x2c5e9b7d4a3f1e83,
+    0xe7193db52a6c8f49,
+    0x6a0b4e8d91f5c237,
+];
+
+/// Key that separates permutations from the other uses of a stream.
+const STREAM_KEY: u64 = 0x3c6ef372fe94f82b;
+
+/// Pseudorandom permutation of 0..n that computes any element, or its inverse,
+/// in expected constant time without memory. Keyed by a Krull64 or Krull65 stream.
+/// The permutation of a key is stable within a `PERMUTATION_VERSION`.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Permutation {
+    /// Number of elements.
+    n: u64,
+    /// Number of bits in each half of the Feistel network.
+    half: u32,
+    /// Key of the Feistel network.
+    key: u64,
+}
+
+impl Permutation {
+    /// Creates a permutation of 0..n keyed by a Krull64 stream.
+    /// Permutations of different lengths are unrelated.
+    pub const fn from_64(n: u64, stream: u64) -> Self {
+        Self::from_key(n, hash(stream ^ STREAM_KEY))
+    }
+
+    /// Creates a permutation of 0..n keyed by a Krull65 stream.
+    /// Permutations of different lengths are unrelated.
+    pub const fn from_128(n: u64, stream: u128) -> Self {
+        Self::from_key(
+            n,
+            hash(stream as u64 ^ hash((stream >> 64) as u64 ^ STREAM_KEY)),
+        )
+    }
+
+    const fn from_key(n: u64, key: u64) -> Self {
+        let bits = 64 - n.saturating_sub(1).leading_zeros();
+        Permutation {
+            n,
+            half: bits.div_ceil(2),
+            key: hash(key ^ n),
+        }
+    }
+
+    /// Returns the number of elements.
+    #[inline]
+    pub const fn len(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns true if the permutation has no elements.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Round function of the Feistel network.
+    #[inline]
+    const fn round(&self, x: u64, round: usize) -> u64 {
+        hash(x ^ self.key ^ ROUND_KEYS[round]) >> (64 - self.half)
+    }
+
+    /// Applies the Feistel network to a 2h-bit number.
+    const fn encrypt(&self, x: u64) -> u64 {
+        let mut l = x >> self.half;
+        let mut r = x & ((1 << self.half) - 1);
+        let mut i = 0;
+        while i < ROUNDS {
+            (l, r) = (r, l ^ self.round(r, i));
+            i += 1;
+        }
+        (l << self.half) | r
+    }
+
+    /// Applies the inverse of the Feistel network to a 2h-bit number.
+    const fn decrypt(&self, x: u64) -> u64 {
+        let mut l = x >> self.half;
+        let mut r = x & ((1 << self.half) - 1);
+        let mut i = ROUNDS;
+        while i > 0 {
+            i -= 1;
+            (l, r) = (r ^ self.round(l, i), l);
+        }
+        (l << self.half) | r
+    }
+
+    /// Returns the element at index i. Panics if i is not less than n.
+    pub const fn apply(&self, i: u64) -> u64 {
+        assert!(i < self.n, "Permutation::apply: index out of range");
+        if self.half == 0 {
+            return i;
+        }
+        let mut x = self.encrypt(i);
+        while x >= self.n {
+            x = self.encrypt(x);
+        }
+        x
+    }
+
+    /// Returns the index of element x: the inverse of `apply`. Panics if x is not less than n.
+    pub const fn invert(&self, x: u64) -> u64 {
+        assert!(x < self.n, "Permutation::invert: element out of range");
+        if self.half == 0 {
+            return x;
+        }
+        let mut i = self.decrypt(x);
+        while i >= self.n {
+            i = self.decrypt(i);
+        }
+        i
+    }
+
+    /// Returns an iterator over the elements in order of index.
+    #[inline]
+    pub fn iter(&self) -> PermutationIter {
+        PermutationIter {
+            permutation: *self,
+            indices: 0..self.n,
+        }
+    }
+}
+
+impl IntoIterator for &Permutation {
+    type Item = u64;
+    type IntoIter = PermutationIter;
+
+    #[inline]
+    fn into_iter(self) -> PermutationIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the elements of a permutation.
+/// Iterates from both ends and skips items with `nth` and `nth_back` in constant time.
+/// Permutations can be longer than `usize`, so the iterator is not an `ExactSizeIterator`:
+/// `len_64` returns the exact length, and `count()` panics if it does not fit.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PermutationIter {
+    permutation: Permutation,
+    /// Indices left.
+    indices: Range<u64>,
+}
+
+impl PermutationIter {
+    /// Returns the number of items left as a `u64`.
+    #[inline]
+    pub fn len_64(&self) -> u64 {
+        self.indices.end - self.indices.start
+    }
+
+    /// Returns the range of indices left.
+    #[inline]
+    pub fn indices(&self) -> Range<u64> {
+        self.indices.clone()
+    }
+}
+
+impl Iterator for PermutationIter {
+    type Item = u64;
+
+    #[inline]
+    fn next(&mut self) -> Option<u64> {
+        self.indices.next().map(|i| self.permutation.apply(i))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.len_64()) {
+            Ok(len) => (len, Some(len)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<u64> {
+        self.indices.nth(n).map(|i| self.permutation.apply(i))
+    }
+
+    fn count(self) -> usize {
+        usize::try_from(self.len_64()).expect("PermutationIter::count: length overflows usize")
+    }
+
+    fn last(mut self) -> Option<u64> {
+        self.next_back()
+    }
+}
+
+impl DoubleEndedIterator for PermutationIter {
+    #[inline]
+    fn next_back(&mut self) -> Option<u64> {
+        self.indices.next_back().map(|i| self.permutation.apply(i))
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<u64> {
+        self.indices.nth_back(n).map(|i| self.permutation.apply(i))
+    }
+}
+
+impl FusedIterator for PermutationIter {}
+
+impl Krull64 {
+    /// Returns a pseudorandom permutation of 0..n keyed by the stream of the generator.
+    /// The position of the generator does not matter.
+    pub fn permutation(&self, n: u64) -> Permutation {
+        Permutation::from_64(n, self.stream())
+    }
+}
+
+impl Krull65 {
+    /// Returns a pseudorandom permutation of 0..n keyed by the stream of the generator.
+    /// The position of the generator does not matter.
+    pub fn permutation(&self, n: u64) -> Permutation {
+        Permutation::from_128(n, self.stream())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    /// Checks that the permutation is a bijection and that `invert` inverts it.
+    fn check_bijection(p: &Permutation) {
+        let mut seen = [false; 4200];
+        for (i, x) in p.iter().enumerate() {
+            assert!(!seen[x as usize]);
+            seen[x as usize] = true;
+            assert_eq!(i as u64, p.invert(x));
+        }
+        assert_eq!(p.len(), seen.iter().filter(|&&x| x).count() as u64);
+    }
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers of version 1. These must never change.
+        assert_eq!(1, PERMUTATION_VERSION);
+        let p = Krull64::from_64(0).permutation(10);
+        assert!(p.iter().eq([1, 5, 7, 0, 8, 4, 6, 9, 2, 3]));
+        let p = Krull65::from_64(0).permutation(1 << 40);
+        assert!(p
+            .iter()
+            .take(4)
+            .eq([1040932262804, 341010647411, 852059647450, 1097128868130]));
+        const P: Permutation = Permutation::from_64(10, 0);
+        const X: u64 = P.apply(3);
+        assert_eq!(0, X);
+        assert_eq!(P, Krull64::from_64(0).permutation(10));
+
+        // Permutations of any length are bijections.
+        for n in (0..=70).chain([255, 256, 257, 1000, 4096, 4097, 4199]) {
+            check_bijection(&Permutation::from_64(n, n ^ 1));
+            check_bijection(&Permutation::from_128(n, (n as u128) << 64));
+        }
+        assert!(Permutation::from_64(0, 1).is_empty());
+        assert_eq!(0, Permutation::from_64(1, 1).apply(0));
+        let mut krull = Krull64::from_64(1);
+        for n in [(1 << 40) + 3, u64::MAX - 1, u64::MAX] {
+            let p = Permutation::from_64(n, 1);
+            for _ in 0..100 {
+                let i = krull.below_u64(n);
+                let x = p.apply(i);
+                assert!(x < n);
+                assert_eq!(i, p.invert(x));
+            }
+        }
+
+        // Iteration works from both ends and skips.
+        let p = Permutation::from_64(100, 2);
+        let mut iter = p.iter();
+        assert_eq!(100, iter.len_64());
+        assert_eq!(Some(p.apply(0)), iter.next());
+        assert_eq!(Some(p.apply(99)), iter.next_back());
+        assert_eq!(Some(p.apply(11)), iter.nth(10));
+        assert_eq!(Some(p.apply(88)), iter.nth_back(10));
+        assert_eq!(12..88, iter.indices());
+        assert_eq!(76, iter.len_64());
+        assert_eq!(76, iter.clone().count());
+        assert_eq!(Some(p.apply(87)), iter.clone().last());
+        assert!(iter.rev().eq((12..88).rev().map(|i| p.apply(i))));
+
+        // Elements land uniformly at each index, and neighbouring elements are independent.
+        let n = 6000;
+        let mut counts = [0u32; 6];
+        let mut ascending = 0;
+        for stream in 0..n {
+            let p = Permutation::from_64(6, stream as u64);
+            counts[p.apply(2) as usize] += 1;
+            ascending += (p.apply(3) < p.apply(4)) as u32;
+        }
+        assert!(counts.iter().all(|&c| c.abs_diff(n / 6) < 150));
+        assert!(ascending.abs_diff(n / 2) < 200);
+        let mut counts = [0u32; 6];
+        for stream in 0..n {
+            let p = Permutation::from_128(6, (stream as u128) << 64);
+            counts[p.invert(2) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c.abs_diff(n / 6) < 150));
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn panic_on_index_out_of_range() {
+        Permutation::from_64(10, 0).apply(10);
+    }
+}
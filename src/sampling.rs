@@ -0,0 +1,344 @@
+use super::math::{exp, ln, ln_1p};
+use super::{Float, Krull64, Krull65, RngCore, Uniform};
+
+// Sampling without replacement into caller-provided buffers. Every algorithm is fixed,
+// regardless of the sizes involved, and draws its random numbers as u64 values,
+// so results are identical on all targets, including 32-bit ones.
+//
+// Combinations of k elements from 0..n are ranked in lexicographic order of their
+// sorted elements: rank 0 is [0, 1, ..., k - 1] and the last rank is [n - k, ..., n - 1].
+// Internally we use the colexicographic order, where the rank of d_0 < d_1 < ... < d_(k-1)
+// is the sum of C(d_i, i + 1). The two orders are related by complementing the elements.
+
+/// Returns the binomial coefficient C(n, k), the number of combinations of k elements
+/// from a set of n elements, or `None` if it does not fit in u128.
+pub fn binomial_coefficient(n: u64, k: u64) -> Option<u128> {
+    if k > n {
+        return Some(0);
+    }
+    let k = k.min(n - k);
+    let mut c: u128 = 1;
+    for j in 1..=k as u128 {
+        // c = C(n - k + j - 1, j - 1), so c * (n - k + j) is divisible by j.
+        // The intermediate values increase, so none overflows if the result does not.
+        let m = (n - k) as u128 + j;
+        let g = gcd(c, j);
+        c = (c / g).checked_mul(m / (j / g))?;
+    }
+    Some(c)
+}
+
+/// Returns the greatest common divisor.
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Returns the rank of the combination in lexicographic order among combinations of
+/// `combination.len()` elements from 0..n. Returns `None` if the elements are not
+/// strictly increasing and less than n, or if the number of combinations does not fit in u128.
+pub fn combination_rank(n: u64, combination: &[u64]) -> Option<u128> {
+    let k = combination.len();
+    if combination.windows(2).any(|w| w[0] >= w[1]) || combination.last() >= Some(&n) {
+        return None;
+    }
+    // The complement n - 1 - c_(k-1-i) lists the elements in increasing order.
+    let mut colex: u128 = 0;
+    for (i, &c) in combination.iter().rev().enumerate() {
+        colex += binomial_coefficient(n - 1 - c, i as u64 + 1)?;
+    }
+    Some(binomial_coefficient(n, k as u64)? - 1 - colex)
+}
+
+/// Writes the combination of `combination.len()` elements from 0..n that has the given rank
+/// in lexicographic order. The elements are in increasing order. Panics if the rank
+/// is not less than the number of combinations, or if that number does not fit in u128.
+pub fn combination_unrank(n: u64, rank: u128, combination: &mut [u64]) {
+    let k = combination.len();
+    let count =
+        binomial_coefficient(n, k as u64).expect("combination_unrank: too many combinations");
+    assert!(rank < count, "combination_unrank: rank out of range");
+    let mut colex = count - 1 - rank;
+    let mut high = n;
+    for i in (0..k).rev() {
+        // Find the largest d < high with C(d, i + 1) <= colex by binary search.
+        // C(i, i + 1) = 0, so d = i always qualifies.
+        let mut low = i as u64;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            match binomial_coefficient(mid, i as u64 + 1) {
+                Some(c) if c <= colex => low = mid,
+                _ => high = mid,
+            }
+        }
+        colex -= binomial_coefficient(low, i as u64 + 1).unwrap();
+        combination[k - 1 - i] = n - 1 - low;
+        high = low;
+    }
+}
+
+/// Advances the iterator by `skip` items and returns the next item.
+fn skip_nth<I: Iterator>(iter: &mut I, mut skip: u64) -> Option<I::Item> {
+    // usize may be narrower than u64.
+    while skip > usize::MAX as u64 {
+        iter.nth(usize::MAX - 1)?;
+        skip -= usize::MAX as u64;
+    }
+    iter.nth(skip as usize)
+}
+
+/// Sampling without replacement for any RNG. Results are identical on all targets,
+/// including 32-bit ones, and stable within a crate version.
+/// Krull64 and Krull65 provide these methods inherently.
+pub trait Sampling: RngCore {
+    /// Fills the buffer with distinct indices chosen uniformly from 0..n, in random order,
+    /// with Floyd's algorithm. Draws one `below_u64` number for each index, and takes time
+    /// quadratic in the length of the buffer. Panics if the buffer is longer than n.
+    fn sample_indices(&mut self, n: u64, indices: &mut [u64]) {
+        let k = indices.len() as u64;
+        assert!(k <= n, "sample_indices: more indices than n");
+        for (i, j) in (n - k..n).enumerate() {
+            let t = self.below_u64(j + 1);
+            // If t is taken, j is not, and it takes the place of t.
+            // This keeps the order uniformly random.
+            if let Some(position) = indices[..i].iter().position(|&x| x == t) {
+                indices[position] = j;
+            }
+            indices[i] = t;
+        }
+    }
+
+    /// Fills the buffer with a uniformly chosen subset of 0..n in increasing order.
+    /// Draws the same numbers as `sample_indices`. Panics if the buffer is longer than n.
+    fn subset(&mut self, n: u64, subset: &mut [u64]) {
+        self.sample_indices(n, subset);
+        subset.sort_unstable();
+    }
+
+    /// Fills the buffer with a uniformly chosen combination of elements from 0..n
+    /// in increasing order and returns its rank in lexicographic order.
+    /// Returns `None` if there are no such combinations or their number does not fit in u128.
+    /// Draws the rank from two `next_u64()` outputs at a time, with a rejection rate below 1/2.
+    fn combination(&mut self, n: u64, combination: &mut [u64]) -> Option<u128> {
+        let count = binomial_coefficient(n, combination.len() as u64)?;
+        if count == 0 {
+            return None;
+        }
+        // Rejection sampling from the smallest power of two not less than the count.
+        let mask = u128::MAX
+            .checked_shr((count - 1).leading_zeros())
+            .unwrap_or(0);
+        let rank = loop {
+            let high = self.next_u64() as u128;
+            let rank = ((high << 64) | self.next_u64() as u128) & mask;
+            if rank < count {
+                break rank;
+            }
+        };
+        combination_unrank(n, rank, combination);
+        Some(rank)
+    }
+
+    /// Fills the reservoir with a uniform sample of the items of the iterator, using
+    /// Li's Algorithm L, and returns the number of items stored, which is less than the
+    /// length of the reservoir only if the iterator runs out first.
+    /// Consumes no outputs until the reservoir is full, and then at least 2 + 3r outputs
+    /// for r replacements (exactly, unless `below_u64` rejects): 2 + 2r floats and r `below_u64`
+    /// numbers. On average r is about k ln(n / k) for n items and a reservoir of k.
+    fn reservoir<I: IntoIterator>(&mut self, iter: I, reservoir: &mut [I::Item]) -> usize {
+        // Li, K.-H., Reservoir-Sampling Algorithms of Time Complexity O(n(1 + log(N/n))) (1994).
+        let mut iter = iter.into_iter();
+        for (i, slot) in reservoir.iter_mut().enumerate() {
+            match iter.next() {
+                Some(item) => *slot = item,
+                None => return i,
+            }
+        }
+        let k = reservoir.len();
+        if k == 0 {
+            return 0;
+        }
+        // w is distributed as the largest of k uniform numbers.
+        let mut w = exp(ln(self.next_f64_open()) / k as f64);
+        loop {
+            // The number of items to skip is geometric with success probability w.
+            // The conversion truncates towards zero and saturates.
+            let skip = (ln(self.next_f64_open()) / ln_1p(-w)) as u64;
+            match skip_nth(&mut iter, skip) {
+                Some(item) => {
+                    reservoir[self.below_u64(k as u64) as usize] = item;
+                    w *= exp(ln(self.next_f64_open()) / k as f64);
+                }
+                None => return k,
+            }
+        }
+    }
+}
+
+impl<R: RngCore + ?Sized> Sampling for R {}
+
+/// Implements the methods of `Sampling` inherently so they can be used without an import.
+macro_rules! impl_sampling {
+    ( $($t:ty),* ) => {
+    $( impl $t {
+        /// Fills the buffer with distinct indices chosen uniformly from 0..n, in random order.
+        /// Panics if the buffer is longer than n.
+        /// Results are identical on all targets and stable within a crate version.
+        #[inline]
+        pub fn sample_indices(&mut self, n: u64, indices: &mut [u64]) {
+            Sampling::sample_indices(self, n, indices)
+        }
+
+        /// Fills the buffer with a uniformly chosen subset of 0..n in increasing order.
+        /// Panics if the buffer is longer than n.
+        /// Results are identical on all targets and stable within a crate version.
+        #[inline]
+        pub fn subset(&mut self, n: u64, subset: &mut [u64]) {
+            Sampling::subset(self, n, subset)
+        }
+
+        /// Fills the buffer with a uniformly chosen combination of elements from 0..n
+        /// in increasing order and returns its rank in lexicographic order.
+        /// Returns `None` if there are no such combinations or their number does not fit in u128.
+        /// Results are identical on all targets and stable within a crate version.
+        #[inline]
+        pub fn combination(&mut self, n: u64, combination: &mut [u64]) -> Option<u128> {
+            Sampling::combination(self, n, combination)
+        }
+
+        /// Fills the reservoir with a uniform sample of the items of the iterator
+        /// and returns the number of items stored.
+        /// Results are identical on all targets and stable within a crate version.
+        #[inline]
+        pub fn reservoir<I: IntoIterator>(&mut self, iter: I, reservoir: &mut [I::Item]) -> usize {
+            Sampling::reservoir(self, iter, reservoir)
+        }
+    }) *
+    }
+}
+impl_sampling! { Krull64, Krull65 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers, which are the same on all targets. These must not change
+        // within a crate version.
+        let mut krull = Krull64::from_64(0);
+        let mut indices = [0; 5];
+        krull.sample_indices(100, &mut indices);
+        assert_eq!([32, 11, 61, 95, 76], indices);
+        assert_eq!(5, krull.position());
+        krull.subset(100, &mut indices);
+        assert_eq!([3, 13, 22, 52, 64], indices);
+        assert_eq!(10, krull.position());
+        assert_eq!(Some(66466994), krull.combination(100, &mut indices));
+        assert_eq!([34, 37, 73, 79, 97], indices);
+        assert_eq!(14, krull.position());
+        let mut reservoir = [0; 5];
+        assert_eq!(5, krull.reservoir(0..1000u32, &mut reservoir));
+        assert_eq!([65, 781, 915, 372, 528], reservoir);
+        assert_eq!(85, krull.position());
+
+        assert_eq!(Some(1), binomial_coefficient(0, 0));
+        assert_eq!(Some(10), binomial_coefficient(5, 2));
+        assert_eq!(Some(0), binomial_coefficient(5, 6));
+        assert_eq!(Some(1832624140942590534), binomial_coefficient(64, 32));
+        assert_eq!(
+            Some(188694833082770476622296176145946360850),
+            binomial_coefficient(131, 65)
+        );
+        assert_eq!(None, binomial_coefficient(132, 66));
+        assert_eq!(Some(1 << 40), binomial_coefficient(1 << 40, 1));
+
+        // Ranks enumerate combinations in lexicographic order.
+        let mut rank = 0;
+        let mut combination = [0; 3];
+        for a in 0..7 {
+            for b in a + 1..7 {
+                for c in b + 1..7 {
+                    assert_eq!(Some(rank), combination_rank(7, &[a, b, c]));
+                    combination_unrank(7, rank, &mut combination);
+                    assert_eq!([a, b, c], combination);
+                    rank += 1;
+                }
+            }
+        }
+        assert_eq!(Some(35), binomial_coefficient(7, 3));
+        assert_eq!(None, combination_rank(7, &[1, 1, 2]));
+        assert_eq!(None, combination_rank(7, &[1, 2, 7]));
+        assert_eq!(None, combination_rank(200, &[0; 100]));
+        assert_eq!(Some(0), combination_rank(7, &[]));
+        let mut krull = Krull65::from_64(1);
+        let mut combination = [0; 6];
+        for n in [6, 100, 1 << 20] {
+            let rank = krull.combination(n, &mut combination).unwrap();
+            assert!(combination.iter().all(|&x| x < n));
+            assert_eq!(Some(rank), combination_rank(n, &combination));
+        }
+        combination_unrank(1 << 20, 0, &mut combination);
+        assert_eq!([0, 1, 2, 3, 4, 5], combination);
+        assert_eq!(None, krull.combination(5, &mut combination));
+        assert_eq!(None, krull.combination(1 << 40, &mut combination));
+
+        // Every ordering of 3 indices is equally likely, and so is every subset of 2 out of 4.
+        let n = 6000;
+        let mut counts = [0u32; 6];
+        let mut subsets = [0u32; 16];
+        for _ in 0..n {
+            let mut indices = [0; 3];
+            krull.sample_indices(3, &mut indices);
+            let index = match indices {
+                [0, 1, 2] => 0,
+                [0, 2, 1] => 1,
+                [1, 0, 2] => 2,
+                [1, 2, 0] => 3,
+                [2, 0, 1] => 4,
+                _ => 5,
+            };
+            counts[index] += 1;
+            let mut subset = [0; 2];
+            krull.subset(4, &mut subset);
+            assert!(subset[0] < subset[1]);
+            subsets[(1 << subset[0]) | (1 << subset[1])] += 1;
+        }
+        assert!(counts.iter().all(|&c| c.abs_diff(n / 6) < 150));
+        assert!(subsets
+            .iter()
+            .filter(|&&c| c > 0)
+            .all(|&c| c.abs_diff(n / 6) < 150));
+
+        // Every item is equally likely to end up in the reservoir.
+        let mut counts = [0u32; 100];
+        for _ in 0..n {
+            let mut reservoir = [0; 5];
+            krull.reservoir(0..100, &mut reservoir);
+            for &x in reservoir.iter() {
+                counts[x] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&c| c.abs_diff(n * 5 / 100) < 80));
+
+        // Short iterators fill only part of the reservoir, and consume nothing.
+        let position = krull.position();
+        let mut reservoir = [0; 5];
+        assert_eq!(3, krull.reservoir([7, 8, 9], &mut reservoir));
+        assert_eq!([7, 8, 9], reservoir[..3]);
+        assert_eq!(0, krull.reservoir([7, 8, 9], &mut []));
+        assert_eq!(position, krull.position());
+        assert_eq!(5, krull.reservoir(0..5, &mut reservoir));
+        assert_eq!([0, 1, 2, 3, 4], reservoir);
+        assert_eq!(position + 2, krull.position());
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn panic_on_too_many_indices() {
+        Krull64::new().sample_indices(2, &mut [0; 3]);
+    }
+}
@@ -0,0 +1,162 @@
+use super::{Krull64, Krull65, RngCore, Uniform};
+
+// Slice operations draw all their random numbers as bounded u64 values.
+// The results and the number of outputs consumed are independent of the width of usize,
+// so they are identical on all targets, and stable across crate versions.
+
+/// Random operations on slices for any RNG. Results are identical on all targets,
+/// including 32-bit ones, and stable across crate versions.
+/// Krull64 and Krull65 provide these methods inherently.
+pub trait Seq: RngCore {
+    /// Shuffles the slice uniformly with the Fisher-Yates algorithm.
+    /// Draws one `below_u64` number for each element after the first.
+    fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = self.below_u64(i as u64 + 1) as usize;
+            slice.swap(i, j);
+        }
+    }
+
+    /// Moves a uniformly chosen sample of `amount` elements, in random order,
+    /// to the front of the slice. Returns the sample and the remaining elements.
+    /// If `amount` exceeds the length of the slice, the whole slice is shuffled.
+    /// Draws one `below_u64` number for each sampled element.
+    fn partial_shuffle<'a, T>(
+        &mut self,
+        slice: &'a mut [T],
+        amount: usize,
+    ) -> (&'a mut [T], &'a mut [T]) {
+        let amount = amount.min(slice.len());
+        for i in 0..amount {
+            let j = i + self.below_u64((slice.len() - i) as u64) as usize;
+            slice.swap(i, j);
+        }
+        slice.split_at_mut(amount)
+    }
+
+    /// Returns a uniformly chosen element of the slice, or `None` if it is empty.
+    /// Draws one `below_u64` number if the slice is not empty.
+    fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+        if slice.is_empty() {
+            None
+        } else {
+            Some(&slice[self.below_u64(slice.len() as u64) as usize])
+        }
+    }
+
+    /// Returns a uniformly chosen element of the slice, or `None` if it is empty.
+    /// Draws one `below_u64` number if the slice is not empty.
+    fn choose_mut<'a, T>(&mut self, slice: &'a mut [T]) -> Option<&'a mut T> {
+        if slice.is_empty() {
+            None
+        } else {
+            let i = self.below_u64(slice.len() as u64) as usize;
+            Some(&mut slice[i])
+        }
+    }
+}
+
+impl<R: RngCore + ?Sized> Seq for R {}
+
+/// Implements the methods of `Seq` inherently so they can be used without an import.
+macro_rules! impl_seq {
+    ( $($t:ty),* ) => {
+    $( impl $t {
+        /// Shuffles the slice uniformly.
+        /// Results are identical on all targets and stable across crate versions.
+        #[inline]
+        pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+            Seq::shuffle(self, slice)
+        }
+
+        /// Moves a uniformly chosen sample of `amount` elements, in random order,
+        /// to the front of the slice. Returns the sample and the remaining elements.
+        /// Results are identical on all targets and stable across crate versions.
+        #[inline]
+        pub fn partial_shuffle<'a, T>(
+            &mut self,
+            slice: &'a mut [T],
+            amount: usize,
+        ) -> (&'a mut [T], &'a mut [T]) {
+            Seq::partial_shuffle(self, slice, amount)
+        }
+
+        /// Returns a uniformly chosen element of the slice, or `None` if it is empty.
+        /// Results are identical on all targets and stable across crate versions.
+        #[inline]
+        pub fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+            Seq::choose(self, slice)
+        }
+
+        /// Returns a uniformly chosen element of the slice, or `None` if it is empty.
+        /// Results are identical on all targets and stable across crate versions.
+        #[inline]
+        pub fn choose_mut<'a, T>(&mut self, slice: &'a mut [T]) -> Option<&'a mut T> {
+            Seq::choose_mut(self, slice)
+        }
+    }) *
+    }
+}
+impl_seq! { Krull64, Krull65 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers, which are the same on all targets. These must never change.
+        let mut krull = Krull64::from_64(0);
+        let mut items: [u8; 16] = core::array::from_fn(|i| i as u8);
+        krull.shuffle(&mut items);
+        assert_eq!(
+            [15, 10, 11, 7, 6, 3, 13, 0, 4, 14, 2, 9, 12, 8, 1, 5],
+            items
+        );
+        assert_eq!(15, krull.position());
+        let mut items: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let (sample, rest) = krull.partial_shuffle(&mut items, 4);
+        assert_eq!([8, 0, 10, 9], sample);
+        assert_eq!(12, rest.len());
+        assert_eq!(19, krull.position());
+        assert_eq!(Some(&15), krull.choose(&items));
+        assert_eq!(20, krull.position());
+        assert_eq!(None, krull.choose::<u8>(&[]));
+        assert_eq!(None, krull.choose_mut::<u8>(&mut []));
+        assert_eq!(20, krull.position());
+
+        // Shuffles are permutations. Partial shuffles clamp the amount.
+        let mut krull = Krull65::from_64(1);
+        let mut items: [u32; 50] = core::array::from_fn(|i| i as u32);
+        krull.shuffle(&mut items);
+        let position = krull.position();
+        let (sample, rest) = krull.partial_shuffle(&mut items, 100);
+        assert_eq!(50, sample.len());
+        assert!(rest.is_empty());
+        assert_eq!(position + 50, krull.position());
+        items.sort_unstable();
+        assert!(items.iter().enumerate().all(|(i, &x)| i as u32 == x));
+
+        // Every permutation of 3 elements is equally likely.
+        let mut counts = [0u32; 6];
+        let n = 6000;
+        for _ in 0..n {
+            let mut items = [0, 1, 2];
+            krull.shuffle(&mut items);
+            let index = match items {
+                [0, 1, 2] => 0,
+                [0, 2, 1] => 1,
+                [1, 0, 2] => 2,
+                [1, 2, 0] => 3,
+                [2, 0, 1] => 4,
+                _ => 5,
+            };
+            counts[index] += 1;
+        }
+        assert!(counts.iter().all(|&c| c.abs_diff(n / 6) < 150));
+
+        let mut items = [0u8; 4];
+        *krull.choose_mut(&mut items).unwrap() = 1;
+        assert_eq!(1, items.iter().sum::<u8>());
+    }
+}
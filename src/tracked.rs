@@ -0,0 +1,291 @@
+use super::{Error, Krull64, Krull65, Outputs, RandomAccess, RngCore};
+use core::ops::Range;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Querying the position of a Krull generator involves solving a discrete logarithm,
+// which takes 128 iterations. Tracked keeps a position counter on the side
+// that is updated incrementally as the generator moves.
+
+/// Random access RNG that keeps track of its stream and position,
+/// making `position()` and `stream()` constant time queries.
+/// Outputs are identical to those of the wrapped RNG.
+/// Only the wrapped RNG is serialized; the stream and position are recomputed on deserialization.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        into = "TrackedRng<R>",
+        from = "TrackedRng<R>",
+        bound = "R: Serialize + for<'a> Deserialize<'a>"
+    )
+)]
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Tracked<R: RandomAccess> {
+    /// The wrapped RNG.
+    rng: R,
+    /// Current stream.
+    stream: R::Stream,
+    /// Current position in stream.
+    position: u128,
+}
+
+/// Serialized form of `Tracked`, which omits the redundant stream and position.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Tracked")]
+struct TrackedRng<R> {
+    rng: R,
+}
+
+#[cfg(feature = "serde")]
+impl<R: RandomAccess> From<Tracked<R>> for TrackedRng<R> {
+    fn from(tracked: Tracked<R>) -> Self {
+        TrackedRng { rng: tracked.rng }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<R: RandomAccess> From<TrackedRng<R>> for Tracked<R> {
+    fn from(serialized: TrackedRng<R>) -> Self {
+        Tracked::new(serialized.rng)
+    }
+}
+
+/// Krull64 RNG that keeps track of its position.
+pub type TrackedKrull64 = Tracked<Krull64>;
+
+/// Krull65 RNG that keeps track of its position.
+pub type TrackedKrull65 = Tracked<Krull65>;
+
+impl<R: RandomAccess> Tracked<R> {
+    /// Wraps an RNG, starting from its current stream and position.
+    pub fn new(rng: R) -> Self {
+        Tracked {
+            stream: rng.stream(),
+            position: rng.position(),
+            rng,
+        }
+    }
+
+    /// Returns the wrapped RNG.
+    #[inline]
+    pub fn inner(&self) -> &R {
+        &self.rng
+    }
+
+    /// Unwraps the RNG.
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.rng
+    }
+
+    /// Returns the current 64-bit output.
+    #[inline]
+    pub fn get(&self) -> u64 {
+        self.rng.get()
+    }
+
+    /// Generates the next 64-bit random number.
+    #[inline]
+    pub fn step(&mut self) -> u64 {
+        self.position = self.position.wrapping_add(1);
+        self.rng.step()
+    }
+
+    /// Steps back to the previous position and returns the 64-bit output there.
+    #[inline]
+    pub fn step_back(&mut self) -> u64 {
+        self.position = self.position.wrapping_sub(1);
+        self.rng.step_back()
+    }
+
+    /// Jumps forward (if steps > 0) or backward (if steps < 0) or does nothing (if steps = 0).
+    /// The stream wraps around, so signed steps can be interpreted as unsigned.
+    pub fn jump(&mut self, steps: i128) {
+        self.position = self.position.wrapping_add(steps as u128);
+        self.rng.jump(steps);
+    }
+
+    /// Returns current position in stream. The full state of the generator is (stream, position).
+    #[inline]
+    pub fn position(&self) -> u128 {
+        self.position
+    }
+
+    /// Sets position in stream.
+    pub fn set_position(&mut self, position: u128) {
+        self.position = position;
+        self.rng.set_position(position);
+    }
+
+    /// Resets stream position to 0. Equivalent to set_position(0).
+    #[inline]
+    pub fn reset(&mut self) {
+        self.position = 0;
+        self.rng.reset();
+    }
+
+    /// Returns current stream. The full state of the generator is (stream, position).
+    #[inline]
+    pub fn stream(&self) -> R::Stream {
+        self.stream
+    }
+
+    /// Sets stream and initializes position to 0.
+    pub fn set_stream(&mut self, stream: R::Stream) {
+        self.stream = stream;
+        self.position = 0;
+        self.rng.set_stream(stream);
+    }
+
+    /// Returns an iterator over the outputs at the given range of positions
+    /// in the current stream.
+    pub fn outputs(&self, positions: Range<u128>) -> Outputs<R> {
+        Outputs::new(&self.rng, positions)
+    }
+}
+
+impl<R: RandomAccess> From<R> for Tracked<R> {
+    fn from(rng: R) -> Self {
+        Tracked::new(rng)
+    }
+}
+
+impl<R: RandomAccess> RandomAccess for Tracked<R> {
+    type Stream = R::Stream;
+
+    #[inline]
+    fn get(&self) -> u64 {
+        Tracked::get(self)
+    }
+
+    #[inline]
+    fn step(&mut self) -> u64 {
+        Tracked::step(self)
+    }
+
+    #[inline]
+    fn step_back(&mut self) -> u64 {
+        Tracked::step_back(self)
+    }
+
+    fn jump(&mut self, steps: i128) {
+        Tracked::jump(self, steps)
+    }
+
+    #[inline]
+    fn position(&self) -> u128 {
+        Tracked::position(self)
+    }
+
+    fn set_position(&mut self, position: u128) {
+        Tracked::set_position(self, position)
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        Tracked::reset(self)
+    }
+
+    #[inline]
+    fn stream(&self) -> R::Stream {
+        Tracked::stream(self)
+    }
+
+    fn set_stream(&mut self, stream: R::Stream) {
+        Tracked::set_stream(self, stream)
+    }
+
+    fn output_at(stream: R::Stream, position: u128) -> u64 {
+        R::output_at(stream, position)
+    }
+}
+
+impl<R: RandomAccess + RngCore> RngCore for Tracked<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.step() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.step()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.position = self.position.wrapping_add(((dest.len() as u128) + 7) >> 3);
+        self.rng.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    fn test_tracked<R>(rng: R, stream: R::Stream)
+    where
+        R: RandomAccess + RngCore + PartialEq + core::fmt::Debug,
+    {
+        let mut r: u128 = 0;
+        let mut rnd = || -> u128 {
+            r = r.wrapping_mul(LCG_M128_1).wrapping_add(0xffff);
+            r
+        };
+
+        let mut tracked = Tracked::new(rng.clone());
+        let mut reference = rng;
+        for _ in 0..1 << 10 {
+            match rnd() >> 125 {
+                0 => assert_eq!(reference.step(), tracked.step()),
+                1 => assert_eq!(reference.step_back(), tracked.step_back()),
+                2 => {
+                    let steps = (rnd() >> 64) as i128 - (1 << 63);
+                    reference.jump(steps);
+                    tracked.jump(steps);
+                }
+                3 => {
+                    let position = rnd();
+                    reference.set_position(position);
+                    tracked.set_position(position);
+                }
+                4 => {
+                    reference.reset();
+                    tracked.reset();
+                }
+                5 => {
+                    reference.set_stream(stream);
+                    tracked.set_stream(stream);
+                }
+                6 => {
+                    let bytes = (rnd() >> 120) as usize;
+                    let mut buffer1 = [0u8; 0x100];
+                    let mut buffer2 = [0u8; 0x100];
+                    reference.fill_bytes(&mut buffer1[0..bytes]);
+                    tracked.fill_bytes(&mut buffer2[0..bytes]);
+                    assert_eq!(buffer1, buffer2);
+                }
+                _ => assert_eq!(reference.next_u64(), tracked.next_u64()),
+            }
+            assert_eq!(reference.get(), tracked.get());
+            assert_eq!(reference.position(), tracked.position());
+            assert_eq!(reference.stream(), tracked.stream());
+        }
+        assert_eq!(reference, tracked.into_inner());
+    }
+
+    #[test]
+    pub fn run_tests() {
+        test_tracked(Krull64::from_64(1), 2);
+        test_tracked(Krull65::from_128(1 << 64), 1 << 100);
+        let mut krull = Krull64::from_128(0x1234_5678_9abc_def0_1234_5678_9abc_def0);
+        let mut tracked = TrackedKrull64::from(krull.clone());
+        assert!(krull.outputs(0x10..0x20).eq(tracked.outputs(0x10..0x20)));
+        assert_eq!(krull.next_u64(), tracked.next_u64());
+        assert_eq!(krull.position(), tracked.position());
+    }
+}
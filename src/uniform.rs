@@ -0,0 +1,246 @@
+use super::{Krull64, Krull65, RngCore};
+use core::ops::RangeInclusive;
+
+// Bounded integers are drawn with Lemire's nearly divisionless method:
+// Lemire, D., Fast Random Integer Generation in an Interval (2019).
+// A random number x is scaled to [0, n) by taking the high half of x * n,
+// rejecting the few low halves that would bias the result.
+//
+// The algorithms and the number of outputs they consume are part of the stable API:
+// for a given generator state, results never change across crate versions or platforms.
+
+/// Unbiased bounded random numbers for any RNG. Results are stable across
+/// crate versions and platforms. Krull64 and Krull65 provide these methods inherently.
+pub trait Uniform: RngCore {
+    /// Returns a uniformly distributed number in 0..n. Panics if n is zero.
+    /// Consumes one `next_u32()` output, and more only in the rare case of rejection.
+    fn below_u32(&mut self, n: u32) -> u32 {
+        assert!(n > 0, "below_u32: n must be positive");
+        let mut m = self.next_u32() as u64 * n as u64;
+        if (m as u32) < n {
+            // Rejection threshold is 2**32 mod n.
+            let t = n.wrapping_neg() % n;
+            while (m as u32) < t {
+                m = self.next_u32() as u64 * n as u64;
+            }
+        }
+        (m >> 32) as u32
+    }
+
+    /// Returns a uniformly distributed number in 0..n. Panics if n is zero.
+    /// Consumes one `next_u64()` output, and more only in the rare case of rejection.
+    fn below_u64(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "below_u64: n must be positive");
+        let mut m = self.next_u64() as u128 * n as u128;
+        if (m as u64) < n {
+            // Rejection threshold is 2**64 mod n.
+            let t = n.wrapping_neg() % n;
+            while (m as u64) < t {
+                m = self.next_u64() as u128 * n as u128;
+            }
+        }
+        (m >> 64) as u64
+    }
+
+    /// Returns a uniformly distributed number in the inclusive range.
+    /// Panics if the range is empty.
+    fn range_u32(&mut self, range: RangeInclusive<u32>) -> u32 {
+        let (a, b) = range.into_inner();
+        assert!(a <= b, "range_u32: range must not be empty");
+        match (b - a).checked_add(1) {
+            Some(n) => a + self.below_u32(n),
+            None => self.next_u32(),
+        }
+    }
+
+    /// Returns a uniformly distributed number in the inclusive range.
+    /// Panics if the range is empty.
+    fn range_u64(&mut self, range: RangeInclusive<u64>) -> u64 {
+        let (a, b) = range.into_inner();
+        assert!(a <= b, "range_u64: range must not be empty");
+        match (b - a).checked_add(1) {
+            Some(n) => a + self.below_u64(n),
+            None => self.next_u64(),
+        }
+    }
+
+    /// Returns a uniformly distributed number in the inclusive range.
+    /// Panics if the range is empty.
+    fn range_i32(&mut self, range: RangeInclusive<i32>) -> i32 {
+        let (a, b) = range.into_inner();
+        assert!(a <= b, "range_i32: range must not be empty");
+        match (b.wrapping_sub(a) as u32).checked_add(1) {
+            Some(n) => a.wrapping_add(self.below_u32(n) as i32),
+            None => self.next_u32() as i32,
+        }
+    }
+
+    /// Returns a uniformly distributed number in the inclusive range.
+    /// Panics if the range is empty.
+    fn range_i64(&mut self, range: RangeInclusive<i64>) -> i64 {
+        let (a, b) = range.into_inner();
+        assert!(a <= b, "range_i64: range must not be empty");
+        match (b.wrapping_sub(a) as u64).checked_add(1) {
+            Some(n) => a.wrapping_add(self.below_u64(n) as i64),
+            None => self.next_u64() as i64,
+        }
+    }
+
+    /// Returns a random bool. Consumes one `next_u64()` output and returns its highest bit.
+    fn bool(&mut self) -> bool {
+        (self.next_u64() >> 63) != 0
+    }
+
+    /// Returns true with probability p, which is clamped to [0, 1].
+    /// The probability is realized exactly to 64 bits of precision.
+    /// Always consumes one `next_u64()` output.
+    fn chance(&mut self, p: f64) -> bool {
+        let x = self.next_u64();
+        if p >= 1.0 {
+            true
+        } else {
+            // Scaling by a power of two is exact. Negative and NaN p convert to 0.
+            x < (p * 18446744073709551616.0) as u64
+        }
+    }
+}
+
+impl<R: RngCore + ?Sized> Uniform for R {}
+
+/// Implements the methods of `Uniform` inherently so they can be used without an import.
+macro_rules! impl_uniform {
+    ( $($t:ty),* ) => {
+    $( impl $t {
+        /// Returns a uniformly distributed number in 0..n. Panics if n is zero.
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn below_u32(&mut self, n: u32) -> u32 {
+            Uniform::below_u32(self, n)
+        }
+
+        /// Returns a uniformly distributed number in 0..n. Panics if n is zero.
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn below_u64(&mut self, n: u64) -> u64 {
+            Uniform::below_u64(self, n)
+        }
+
+        /// Returns a uniformly distributed number in the inclusive range.
+        /// Panics if the range is empty. Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn range_u32(&mut self, range: RangeInclusive<u32>) -> u32 {
+            Uniform::range_u32(self, range)
+        }
+
+        /// Returns a uniformly distributed number in the inclusive range.
+        /// Panics if the range is empty. Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn range_u64(&mut self, range: RangeInclusive<u64>) -> u64 {
+            Uniform::range_u64(self, range)
+        }
+
+        /// Returns a uniformly distributed number in the inclusive range.
+        /// Panics if the range is empty. Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn range_i32(&mut self, range: RangeInclusive<i32>) -> i32 {
+            Uniform::range_i32(self, range)
+        }
+
+        /// Returns a uniformly distributed number in the inclusive range.
+        /// Panics if the range is empty. Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn range_i64(&mut self, range: RangeInclusive<i64>) -> i64 {
+            Uniform::range_i64(self, range)
+        }
+
+        /// Returns a random bool.
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn bool(&mut self) -> bool {
+            Uniform::bool(self)
+        }
+
+        /// Returns true with probability p, which is clamped to [0, 1].
+        /// Results are stable across crate versions and platforms.
+        #[inline]
+        pub fn chance(&mut self, p: f64) -> bool {
+            Uniform::chance(self, p)
+        }
+    }) *
+    }
+}
+impl_uniform! { Krull64, Krull65 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers. These must never change.
+        let mut krull = Krull64::from_64(0);
+        assert_eq!(3, krull.below_u64(10));
+        assert_eq!(2, krull.below_u32(10));
+        assert_eq!(1, krull.range_i64(-5..=5));
+        assert_eq!(4, krull.range_i32(-5..=5));
+        assert_eq!(176, krull.range_u64(100..=200));
+        assert_eq!(198, krull.range_u32(100..=200));
+        assert!(!krull.bool());
+        assert!(!krull.chance(0.5));
+        assert_eq!(8, krull.position());
+
+        let mut krull = Krull65::from_64(1);
+        assert_eq!(0, krull.below_u32(1));
+        assert_eq!(0, krull.below_u64(1));
+        assert_eq!(7, krull.range_i64(7..=7));
+        let x = krull.clone().step();
+        assert_eq!(x as i64, krull.clone().range_i64(i64::MIN..=i64::MAX));
+        assert_eq!(x, krull.clone().range_u64(0..=u64::MAX));
+        assert_eq!(x as u32, krull.clone().range_u32(0..=u32::MAX));
+        assert_eq!(x as i32, krull.clone().range_i32(i32::MIN..=i32::MAX));
+        let position = krull.position();
+        assert!(krull.chance(1.0));
+        assert!(!krull.chance(0.0));
+        assert!(!krull.chance(-1.0));
+        assert!(!krull.chance(f64::NAN));
+        assert_eq!(position + 4, krull.position());
+
+        // The trait works for any RNG.
+        let mut tracked = TrackedKrull64::from(Krull64::from_64(0));
+        assert_eq!(
+            Krull64::from_64(0).below_u64(1000),
+            Uniform::below_u64(&mut tracked, 1000)
+        );
+
+        // Counts are uniform. A bound just above 2**63 has the maximum
+        // rejection rate of nearly one half.
+        let mut counts = [0u32; 6];
+        let n = 6000;
+        for _ in 0..n {
+            counts[krull.below_u32(6) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c.abs_diff(n / 6) < 150));
+        let half = (1 << 63) + 1;
+        let mut low = 0;
+        for _ in 0..n {
+            let x = krull.below_u64(half);
+            assert!(x < half);
+            low += (x < 1 << 62) as u32;
+        }
+        assert!(low.abs_diff(n / 2) < 300);
+        let mut heads = 0;
+        for _ in 0..n {
+            let x = krull.range_i64(-3..=2);
+            assert!((-3..=2).contains(&x));
+            heads += krull.chance(0.25) as u32;
+        }
+        assert!(heads.abs_diff(n / 4) < 300);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn panic_on_zero_bound() {
+        Krull64::new().below_u64(0);
+    }
+}
@@ -0,0 +1,448 @@
+use super::{Distribution, Float, RngCore, Uniform};
+use core::cmp::Ordering;
+
+// Weighted choice of an index. Both samplers are built in caller-provided buffers,
+// so they work without allocation:
+// WeightedIndex converts a buffer of weights in place to their cumulative sums and
+// samples with a binary search. Integer weights are sampled exactly.
+// WeightedAlias builds Walker's alias table in O(n) time and samples in O(1) time.
+// Instead of the work lists of Vose's construction it sweeps the weights with
+// two pointers, one for the next light item and one for the current heavy item.
+//
+// The algorithms and the number of outputs they consume are frozen within a crate version.
+
+/// Reason a weighted sampler could not be built.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum WeightError {
+    /// There are no weights.
+    Empty,
+    /// A buffer does not have the same length as the weights.
+    Length,
+    /// A weight is negative or NaN.
+    Invalid,
+    /// The sum of the weights overflows or is not finite.
+    Overflow,
+    /// All weights are zero.
+    Zero,
+}
+
+/// Type that can be used as a weight: an unsigned integer or a float.
+pub trait Weight: Copy + PartialOrd {
+    /// The zero weight.
+    const ZERO: Self;
+
+    /// Returns the sum of two weights, or `None` if it overflows or is not finite.
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    /// Converts the weight to f64.
+    fn to_f64(self) -> f64;
+
+    /// Returns a uniformly distributed weight in [0, total), where total is positive.
+    fn sample_below<R: RngCore + ?Sized>(rng: &mut R, total: Self) -> Self;
+}
+
+impl Weight for u32 {
+    const ZERO: Self = 0;
+
+    #[inline]
+    fn checked_add(self, other: Self) -> Option<Self> {
+        u32::checked_add(self, other)
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    /// Consumes one `next_u32()` output, and more only in the rare case of rejection.
+    #[inline]
+    fn sample_below<R: RngCore + ?Sized>(rng: &mut R, total: Self) -> Self {
+        rng.below_u32(total)
+    }
+}
+
+impl Weight for u64 {
+    const ZERO: Self = 0;
+
+    #[inline]
+    fn checked_add(self, other: Self) -> Option<Self> {
+        u64::checked_add(self, other)
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    /// Consumes one `next_u64()` output, and more only in the rare case of rejection.
+    #[inline]
+    fn sample_below<R: RngCore + ?Sized>(rng: &mut R, total: Self) -> Self {
+        rng.below_u64(total)
+    }
+}
+
+impl Weight for f32 {
+    const ZERO: Self = 0.0;
+
+    #[inline]
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let sum = self + other;
+        if sum.is_finite() {
+            Some(sum)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    /// Consumes one `next_u64()` output. Rounding can return total.
+    #[inline]
+    fn sample_below<R: RngCore + ?Sized>(rng: &mut R, total: Self) -> Self {
+        rng.next_f32() * total
+    }
+}
+
+impl Weight for f64 {
+    const ZERO: Self = 0.0;
+
+    #[inline]
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let sum = self + other;
+        if sum.is_finite() {
+            Some(sum)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    /// Consumes one `next_u64()` output. Rounding can return total.
+    #[inline]
+    fn sample_below<R: RngCore + ?Sized>(rng: &mut R, total: Self) -> Self {
+        rng.next_f64() * total
+    }
+}
+
+/// Checks the weights and returns their sum.
+fn total<W: Weight>(weights: &[W]) -> Result<W, WeightError> {
+    if weights.is_empty() {
+        return Err(WeightError::Empty);
+    }
+    let mut total = W::ZERO;
+    for &w in weights {
+        // NaN is not comparable to zero.
+        if let None | Some(Ordering::Less) = w.partial_cmp(&W::ZERO) {
+            return Err(WeightError::Invalid);
+        }
+        total = total.checked_add(w).ok_or(WeightError::Overflow)?;
+    }
+    if total > W::ZERO {
+        Ok(total)
+    } else {
+        Err(WeightError::Zero)
+    }
+}
+
+/// Weighted choice of an index by binary search of cumulative weights.
+/// Builds in O(n) time and samples in O(log n) time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WeightedIndex<'a, W: Weight> {
+    /// Cumulative sums of the weights.
+    cumulative: &'a [W],
+}
+
+impl<'a, W: Weight> WeightedIndex<'a, W> {
+    /// Creates a sampler that returns each index with probability proportional to its weight.
+    /// The weights are replaced in place with their cumulative sums; on error they are
+    /// left unchanged.
+    pub fn new(weights: &'a mut [W]) -> Result<Self, WeightError> {
+        total(weights)?;
+        let mut sum = W::ZERO;
+        for w in weights.iter_mut() {
+            // The sum was checked already.
+            sum = sum.checked_add(*w).unwrap();
+            *w = sum;
+        }
+        Ok(WeightedIndex {
+            cumulative: weights,
+        })
+    }
+
+    /// Returns the number of weights.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cumulative.len()
+    }
+
+    /// Returns false: there is at least one weight.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns the sum of the weights.
+    #[inline]
+    pub fn total(&self) -> W {
+        self.cumulative[self.cumulative.len() - 1]
+    }
+}
+
+impl<W: Weight> Distribution<usize> for WeightedIndex<'_, W> {
+    /// Consumes one output as `Weight::sample_below` does.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> usize {
+        let total = self.total();
+        let x = W::sample_below(rng, total);
+        let i = self.cumulative.partition_point(|&c| c <= x);
+        if i < self.cumulative.len() {
+            i
+        } else {
+            // Float rounding returned the total. Choose the last item with a positive weight.
+            self.cumulative.partition_point(|&c| c < total)
+        }
+    }
+}
+
+/// Weighted choice of an index with Walker's alias method.
+/// Builds in O(n) time and samples in O(1) time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WeightedAlias<'a> {
+    /// Probability of keeping each index instead of taking its alias.
+    probabilities: &'a [f64],
+    aliases: &'a [usize],
+}
+
+impl<'a> WeightedAlias<'a> {
+    /// Creates a sampler that returns each index with probability proportional to its weight.
+    /// The table is built in the caller's buffers, which must have the same length
+    /// as the weights.
+    pub fn new<W: Weight>(
+        weights: &[W],
+        probabilities: &'a mut [f64],
+        aliases: &'a mut [usize],
+    ) -> Result<Self, WeightError> {
+        let n = weights.len();
+        let scale = n as f64 / total(weights)?.to_f64();
+        if probabilities.len() != n || aliases.len() != n {
+            return Err(WeightError::Length);
+        }
+        // Scale the weights to average 1. An item is light if its scaled weight is less than 1,
+        // and heavy otherwise. Items that are not aliased yet have themselves as aliases.
+        for i in 0..n {
+            probabilities[i] = weights[i].to_f64() * scale;
+            aliases[i] = i;
+        }
+        let next_light = |probabilities: &[f64], aliases: &[usize], start: usize| {
+            (start..n)
+                .find(|&i| probabilities[i] < 1.0 && aliases[i] == i)
+                .unwrap_or(n)
+        };
+        let next_heavy = |probabilities: &[f64], start: usize| {
+            (start..n).find(|&i| probabilities[i] >= 1.0).unwrap_or(n)
+        };
+        let mut light = next_light(probabilities, aliases, 0);
+        let mut heavy = next_heavy(probabilities, 0);
+        // Weight remaining in the current heavy item.
+        let mut w = if heavy < n { probabilities[heavy] } else { 0.0 };
+        while heavy < n {
+            if w >= 1.0 {
+                // Fill up the light item from the heavy item.
+                if light == n {
+                    break;
+                }
+                aliases[light] = heavy;
+                w -= 1.0 - probabilities[light];
+                light = next_light(probabilities, aliases, light + 1);
+            } else {
+                // The heavy item has become light. Fill it up from the next heavy item.
+                let next = next_heavy(probabilities, heavy + 1);
+                if next == n {
+                    break;
+                }
+                probabilities[heavy] = w;
+                aliases[heavy] = next;
+                w = probabilities[next] - (1.0 - w);
+                heavy = next;
+            }
+        }
+        // The items left over, including the last heavy item, have scaled weights
+        // within rounding error of 1.
+        for i in 0..n {
+            if aliases[i] == i {
+                probabilities[i] = 1.0;
+            }
+        }
+        Ok(WeightedAlias {
+            probabilities,
+            aliases,
+        })
+    }
+
+    /// Returns the number of weights.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.probabilities.len()
+    }
+
+    /// Returns false: there is at least one weight.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl Distribution<usize> for WeightedAlias<'_> {
+    /// Consumes two `next_u64()` outputs, and more only in the rare case of rejection
+    /// when choosing the index.
+    fn sample<R: RngCore + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = rng.below_u64(self.probabilities.len() as u64) as usize;
+        if rng.next_f64() < self.probabilities[i] {
+            i
+        } else {
+            self.aliases[i]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    /// Checks that the frequencies of n samples are proportional to the weights.
+    fn check_frequencies<R: RngCore>(
+        rng: &mut R,
+        n: usize,
+        dist: &impl Distribution<usize>,
+        weights: &[f64],
+    ) {
+        let mut counts = [0usize; 16];
+        for _ in 0..n {
+            counts[dist.sample(rng)] += 1;
+        }
+        let total: f64 = weights.iter().sum();
+        for (&count, &w) in counts.iter().zip(weights) {
+            let p = w / total;
+            let expected = n as f64 * p;
+            // Allow 5 standard deviations.
+            let tolerance = 5.0 * math::sqrt(expected * (1.0 - p)) + 1.0e-9;
+            assert!((count as f64 - expected).abs() <= tolerance);
+        }
+    }
+
+    #[test]
+    pub fn run_tests() {
+        // Known answers. These must not change within a crate version.
+        let mut krull = Krull64::from_64(0);
+        let mut weights = [1u32, 2, 3, 4];
+        let index = WeightedIndex::new(&mut weights).unwrap();
+        assert_eq!([1, 3, 6, 10], index.cumulative);
+        let mut probabilities = [0.0; 4];
+        let mut aliases = [0; 4];
+        let alias = WeightedAlias::new(&[1.0, 2.0, 3.0, 4.0], &mut probabilities, &mut aliases);
+        let alias = alias.unwrap();
+        let samples: [usize; 4] = core::array::from_fn(|_| index.sample(&mut krull));
+        assert_eq!([3, 1, 3, 3], samples);
+        let samples: [usize; 4] = core::array::from_fn(|_| alias.sample(&mut krull));
+        assert_eq!([3, 2, 2, 3], samples);
+        assert_eq!(12, krull.position());
+
+        let mut probabilities = [0.0; 3];
+        let mut aliases = [0; 3];
+        let mut empty: [u64; 0] = [];
+        assert_eq!(Err(WeightError::Empty), WeightedIndex::new(&mut empty));
+        assert_eq!(Err(WeightError::Zero), WeightedIndex::new(&mut [0u64, 0]));
+        assert_eq!(
+            Err(WeightError::Invalid),
+            WeightedIndex::new(&mut [1.0, -1.0])
+        );
+        assert_eq!(
+            Err(WeightError::Invalid),
+            WeightedIndex::new(&mut [f32::NAN])
+        );
+        assert_eq!(
+            Err(WeightError::Overflow),
+            WeightedIndex::new(&mut [u32::MAX, 1])
+        );
+        let mut weights = [1.0, f64::INFINITY];
+        assert_eq!(Err(WeightError::Overflow), WeightedIndex::new(&mut weights));
+        assert_eq!([1.0, f64::INFINITY], weights);
+        assert_eq!(
+            Err(WeightError::Length),
+            WeightedAlias::new(&[1u32, 2], &mut probabilities, &mut aliases).map(|_| ())
+        );
+        assert_eq!(
+            Err(WeightError::Zero),
+            WeightedAlias::new(&[0.0f32; 3], &mut probabilities, &mut aliases).map(|_| ())
+        );
+
+        // Alias tables reproduce the weights exactly up to rounding,
+        // including zero weights and equal weights.
+        let mut krull = Krull65::from_64(1);
+        for n in 1..=16 {
+            let mut weights = [0.0; 16];
+            for w in weights[..n].iter_mut() {
+                *w = match krull.below_u32(4) {
+                    0 => 0.0,
+                    1 => 1.0,
+                    _ => krull.next_f64() * 10.0,
+                };
+            }
+            weights[krull.below_u64(n as u64) as usize] += 1.0;
+            let weights = &weights[..n];
+            let mut probabilities = [0.0; 16];
+            let mut aliases = [0; 16];
+            let alias =
+                WeightedAlias::new(weights, &mut probabilities[..n], &mut aliases[..n]).unwrap();
+            assert_eq!(n, alias.len());
+            let total: f64 = weights.iter().sum();
+            for (i, &w) in weights.iter().enumerate() {
+                let mut p = alias.probabilities[i];
+                for j in 0..n {
+                    if alias.aliases[j] == i && j != i {
+                        p += 1.0 - alias.probabilities[j];
+                    }
+                }
+                assert!((p / n as f64 - w / total).abs() < 1.0e-12);
+            }
+        }
+
+        // Sample frequencies follow the weights. Items with zero weight never appear.
+        let n = 1 << 14;
+        let weights = [5.0, 0.0, 1.0, 0.5, 0.0, 10.0, 2.5, 1.0];
+        let mut probabilities = [0.0; 8];
+        let mut aliases = [0; 8];
+        let alias = WeightedAlias::new(&weights, &mut probabilities, &mut aliases).unwrap();
+        check_frequencies(&mut krull, n, &alias, &weights);
+        let mut cumulative = weights;
+        let index = WeightedIndex::new(&mut cumulative).unwrap();
+        assert_eq!(20.0, index.total());
+        check_frequencies(&mut krull, n, &index, &weights);
+        let mut cumulative = weights.map(|w| w as f32);
+        check_frequencies(
+            &mut krull,
+            n,
+            &WeightedIndex::new(&mut cumulative).unwrap(),
+            &weights,
+        );
+        let mut cumulative = weights.map(|w| (w * 2.0) as u64);
+        check_frequencies(
+            &mut krull,
+            n,
+            &WeightedIndex::new(&mut cumulative).unwrap(),
+            &weights,
+        );
+
+        // Large integer weights are sampled exactly.
+        let mut cumulative = [u64::MAX - 1, 1];
+        let index = WeightedIndex::new(&mut cumulative).unwrap();
+        assert!((0..1000).all(|_| index.sample(&mut krull) == 0));
+    }
+}